@@ -0,0 +1,49 @@
+// the gRPC service of --grpc-addr, generated from proto/admin.proto. protox parses it, so building doesn't need protoc
+use prost::Message;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=proto/admin.proto");
+    git_commit();
+
+    let descriptors = protox::compile(["proto/admin.proto"], ["proto"])?;
+    let path = std::path::PathBuf::from(std::env::var("OUT_DIR")?).join("admin_descriptors.bin");
+    std::fs::write(&path, descriptors.encode_to_vec())?;
+    tonic_build::configure()
+        .build_client(false)
+        // replies are read out of the admin API's json, so both always answer the same
+        .type_attribute(".", "#[derive(serde::Deserialize)] #[serde(default)]")
+        .file_descriptor_set_path(&path)
+        .skip_protoc_run()
+        .compile(&["proto/admin.proto"], &["proto"])?;
+    Ok(())
+}
+
+// the first 4 bytes of the commit OE is built from, for the commit engine_getClientVersionV1 answers with.
+// zeros outside a git checkout, like a source tarball
+fn git_commit() {
+    let commit = std::process::Command::new("git")
+        .args(["rev-parse", "--short=8", "HEAD"])
+        .output()
+        .ok()
+        .filter(|v| v.status.success())
+        .and_then(|v| String::from_utf8(v.stdout).ok())
+        .map(|v| v.trim().to_owned())
+        .filter(|v| v.len() == 8 && v.chars().all(|c| c.is_ascii_hexdigit()))
+        .unwrap_or_else(|| "00000000".to_owned());
+    println!("cargo:rustc-env=OE_GIT_COMMIT={}", commit);
+
+    // built again on a new commit, only the files that are there, a missing one would build it every time
+    let head = std::path::Path::new(".git/HEAD");
+    if head.exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        if let Some(v) = std::fs::read_to_string(head).ok().and_then(|v| v.strip_prefix("ref: ").map(|v| v.trim().to_owned())) {
+            let reference = format!(".git/{}", v);
+            if std::path::Path::new(&reference).exists() {
+                println!("cargo:rerun-if-changed={}", reference);
+            } else if std::path::Path::new(".git/packed-refs").exists() {
+                println!("cargo:rerun-if-changed=.git/packed-refs");
+            }
+        }
+    }
+}
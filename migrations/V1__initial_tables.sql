@@ -0,0 +1,6 @@
+-- the tables openexecution has always created, IF NOT EXISTS so databases from before migrations pick up from here
+CREATE TABLE IF NOT EXISTS fcu (request TEXT NOT NULL UNIQUE, response TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS newpayload (request TEXT NOT NULL UNIQUE, response TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS exchangeconfig (response TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS payloadbodies (blockhash TEXT NOT NULL UNIQUE, body TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS payloadnumbers (blocknumber BIGINT NOT NULL UNIQUE, blockhash TEXT NOT NULL);
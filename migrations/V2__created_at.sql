@@ -0,0 +1,9 @@
+-- when each row went in, so the cache can be pruned by age. rows from before this get the time of the migration
+ALTER TABLE fcu ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE newpayload ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE payloadbodies ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE payloadnumbers ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+CREATE INDEX IF NOT EXISTS fcu_created_at ON fcu (created_at);
+CREATE INDEX IF NOT EXISTS newpayload_created_at ON newpayload (created_at);
+CREATE INDEX IF NOT EXISTS payloadbodies_created_at ON payloadbodies (created_at);
+CREATE INDEX IF NOT EXISTS payloadnumbers_created_at ON payloadnumbers (created_at);
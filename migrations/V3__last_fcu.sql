@@ -0,0 +1,2 @@
+-- the canonical CL's last VALID fcU and its response, written on shutdown so a restart picks up where it left off
+CREATE TABLE IF NOT EXISTS lastfcu (pair TEXT NOT NULL);
@@ -0,0 +1,2 @@
+-- the followers seen so far, so the roster survives a restart
+CREATE TABLE IF NOT EXISTS clients (name TEXT PRIMARY KEY, record TEXT NOT NULL);
@@ -0,0 +1,2 @@
+-- the followers and addresses an admin banned, so a restart doesn't lift the bans
+CREATE TABLE IF NOT EXISTS bans (client TEXT PRIMARY KEY, record TEXT NOT NULL);
@@ -0,0 +1,47 @@
+// which hosts may reach the follower endpoint at all, decided on the peer address before a byte of the request is read
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+#[derive(Clone)]
+pub struct IpFilter {
+    allow: Vec<IpNet>, // empty lets in everyone who isn't denied
+    deny: Vec<IpNet>,
+}
+
+// a network like 10.0.0.0/8, or a single address
+#[inline(always)]
+pub fn parse_net(input: &str) -> Result<IpNet, String> {
+    let input = input.trim();
+    input
+        .parse::<IpNet>()
+        .or_else(|_| input.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("{} is not a CIDR network or an IP address", input))
+}
+
+impl IpFilter {
+    pub fn new<'a>(
+        allow: impl IntoIterator<Item = &'a str>,
+        deny: impl IntoIterator<Item = &'a str>,
+    ) -> Result<IpFilter, String> {
+        Ok(IpFilter {
+            allow: allow.into_iter().map(parse_net).collect::<Result<_, _>>()?,
+            deny: deny.into_iter().map(parse_net).collect::<Result<_, _>>()?,
+        })
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    // a denied address stays out even when a wider allowed network has it.
+    // a v4 peer on a dual-stack listener shows up as ::ffff:a.b.c.d, it's matched against the v4 networks
+    #[inline(always)]
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        if self.deny.iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip))
+    }
+}
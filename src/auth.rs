@@ -0,0 +1,389 @@
+// jwt secrets and the tokens signed with them, for the EL and for every CL that talks to openexecution
+use jsonwebtoken::{DecodingKey, EncodingKey, Validation};
+use serde::Deserialize;
+use std::{fmt, sync::Arc, time::Duration, time::SystemTime};
+use tokio::sync::RwLock;
+
+const DEFAULT_ALGORITHM: jsonwebtoken::Algorithm = jsonwebtoken::Algorithm::HS256;
+
+// a secret as the engine API writes it, 32 bytes of hex with or without 0x
+#[inline(always)]
+pub fn parse_secret(input: &str) -> Result<Vec<u8>, String> {
+    let input = input.trim();
+    hex::decode(input.strip_prefix("0x").unwrap_or(input)).map_err(|e| e.to_string())
+}
+
+// a new secret in the format parse_secret reads back, 32 random bytes as 0x hex
+#[inline(always)]
+pub fn generate_secret() -> String {
+    format!("0x{}", hex::encode(rand::random::<[u8; 32]>()))
+}
+
+#[inline(always)]
+pub fn read_secret(path: &str) -> Result<Vec<u8>, String> {
+    let file = std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    parse_secret(&file).map_err(|e| format!("Unable to decode {}: {}", path, e))
+}
+
+// the secret the controlling CL signs with, and the one openexecution signs with toward the EL.
+// they're the same file unless openexecution sits between two trust domains
+pub struct JwtKeys {
+    pub encoding: EncodingKey, // toward the EL
+    pub decoding: DecodingKey, // from the controlling CL
+}
+
+impl JwtKeys {
+    pub fn load(canonical_path: &str, el_path: &str) -> Result<JwtKeys, String> {
+        Ok(JwtKeys {
+            encoding: EncodingKey::from_secret(&read_secret(el_path)?),
+            decoding: DecodingKey::from_secret(&read_secret(canonical_path)?),
+        })
+    }
+}
+
+// the engine API's tokens don't expire, they're only fresh for as long as their iat is close to now
+#[inline(always)]
+fn validation() -> Validation {
+    let mut validation = Validation::new(DEFAULT_ALGORITHM);
+    validation.validate_exp = false;
+    validation.required_spec_claims = Default::default();
+    validation
+}
+
+#[derive(Deserialize)]
+struct TokenClaims {
+    iat: Option<i64>,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    clv: Option<String>,
+}
+
+// the engine API's optional id and clv claims, which CL and which version of it signed a token
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub id: Option<String>,
+    pub clv: Option<String>,
+}
+
+impl From<TokenClaims> for ClientInfo {
+    fn from(claims: TokenClaims) -> Self {
+        ClientInfo {
+            id: claims.id,
+            clv: claims.clv,
+        }
+    }
+}
+
+// the claims without checking who signed them, nothing read from here can be trusted
+#[inline(always)]
+fn unverified_claims(token: &str) -> Option<TokenClaims> {
+    let mut validation = validation();
+    validation.insecure_disable_signature_validation();
+    jsonwebtoken::decode::<TokenClaims>(token, &DecodingKey::from_secret(&[]), &validation)
+        .ok()
+        .map(|v| v.claims)
+}
+
+// the id and clv of a token whose signature can't be checked, only good for telling CLs apart in the logs
+#[inline(always)]
+pub fn peek_client_info(headers: &axum::http::HeaderMap) -> ClientInfo {
+    bearer_token(headers)
+        .ok()
+        .and_then(unverified_claims)
+        .map(ClientInfo::from)
+        .unwrap_or_default()
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    Missing,
+    Invalid(String),
+    MissingIat,
+    CertificateOnly(String), // the token's id names a client without a secret
+    WrongToken, // not --admin-token's
+    Stale { iat: i64, now: i64, max_skew: u64 },
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Missing => write!(f, "Missing JWT"),
+            AuthError::Invalid(e) => write!(f, "Cannot decode JWT: {}", e),
+            AuthError::MissingIat => write!(f, "JWT has no iat claim"),
+            AuthError::CertificateOnly(name) => write!(f, "{} only signs in with a client certificate", name),
+            AuthError::WrongToken => write!(f, "Wrong admin token"),
+            AuthError::Stale { iat, now, max_skew } => write!(
+                f,
+                "JWT iat {} is {}s off from now ({}), more than the allowed {}s",
+                iat,
+                (now - iat).abs(),
+                now,
+                max_skew
+            ),
+        }
+    }
+}
+
+impl AuthError {
+    // what the metrics count it as
+    #[inline(always)]
+    pub fn reason(&self) -> &'static str {
+        match self {
+            AuthError::Missing => "missing",
+            AuthError::Invalid(_) => "invalid",
+            AuthError::MissingIat => "missing_iat",
+            AuthError::CertificateOnly(_) => "certificate_only",
+            AuthError::WrongToken => "wrong_token",
+            AuthError::Stale { .. } => "stale",
+        }
+    }
+}
+
+// the token out of an Authorization: Bearer header
+#[inline(always)]
+pub fn bearer_token(headers: &axum::http::HeaderMap) -> Result<&str, AuthError> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(AuthError::Missing)
+}
+
+// checks the signature, then that iat is within max_skew seconds of now either way
+pub fn verify(token: &str, key: &DecodingKey, max_skew: u64) -> Result<ClientInfo, AuthError> {
+    let claims = jsonwebtoken::decode::<TokenClaims>(token, key, &validation())
+        .map_err(|e| AuthError::Invalid(e.to_string()))?
+        .claims;
+    let iat = claims.iat.ok_or(AuthError::MissingIat)?;
+    let now = chrono::Utc::now().timestamp();
+    if now.abs_diff(iat) > max_skew {
+        return Err(AuthError::Stale { iat, now, max_skew });
+    }
+    Ok(claims.into())
+}
+
+// the follower CLs that are let in, each with its own secret so one can be dropped without re-keying the others.
+// one without a secret can only get in with a client certificate in its name
+pub struct ClientSecrets {
+    clients: Vec<(String, Option<DecodingKey>)>,
+}
+
+impl ClientSecrets {
+    // one client per line, its name then its secret if it has one, blank lines and lines starting with # are skipped
+    pub fn load(path: &str) -> Result<ClientSecrets, String> {
+        let file = std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+
+        let mut clients = Vec::new();
+        for (index, line) in file.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, secret) = match line.split_once(char::is_whitespace) {
+                Some((name, secret)) => (name, Some(secret)),
+                None => (line, None),
+            };
+            if clients.iter().any(|(v, _)| v == name) {
+                return Err(format!("{}:{} repeats the client {}", path, index + 1, name));
+            }
+            let key = match secret {
+                Some(v) => Some(DecodingKey::from_secret(
+                    &parse_secret(v).map_err(|e| format!("{}:{} has a bad secret: {}", path, index + 1, e))?,
+                )),
+                None => None,
+            };
+            clients.push((name.to_owned(), key));
+        }
+
+        if clients.is_empty() {
+            return Err(format!("{} has no clients in it", path));
+        }
+        Ok(ClientSecrets { clients })
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    // the name of the client whose secret signed the token. a token whose id claim names a client has to be
+    // signed by that client, anything else is tried against every client. a token signed by a client but
+    // with a bad iat is that client's error, not a signature mismatch
+    pub fn authenticate(&self, token: &str, max_skew: u64) -> Result<(&str, ClientInfo), AuthError> {
+        let claimed = unverified_claims(token)
+            .and_then(|v| v.id)
+            .and_then(|id| self.clients.iter().find(|(name, _)| *name == id));
+        match claimed {
+            Some((name, Some(key))) => return verify(token, key, max_skew).map(|info| (name.as_str(), info)),
+            Some((name, None)) => return Err(AuthError::CertificateOnly(name.to_owned())),
+            None => {}
+        }
+
+        let mut last_error = AuthError::Invalid("no clients".to_owned());
+        for (name, key) in self.clients.iter().filter_map(|(name, key)| Some((name, key.as_ref()?))) {
+            match verify(token, key, max_skew) {
+                Ok(info) => return Ok((name, info)),
+                Err(AuthError::Invalid(e)) => last_error = AuthError::Invalid(e),
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error)
+    }
+
+    // the client a certificate is for, the first of its CN and SANs that names one
+    #[inline(always)]
+    pub fn certified(&self, cert_names: &[String]) -> Option<&str> {
+        cert_names
+            .iter()
+            .find_map(|cert_name| self.clients.iter().find(|(name, _)| name == cert_name))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+// what the admin API takes, its own bearer token or jwts signed with its own secret, which file it's read from
+#[derive(Debug, Clone)]
+pub enum AdminSecret {
+    Token(String),
+    Jwt(String),
+}
+
+impl AdminSecret {
+    #[inline(always)]
+    pub fn path(&self) -> &str {
+        match self {
+            AdminSecret::Token(v) | AdminSecret::Jwt(v) => v,
+        }
+    }
+}
+
+// never the engine API's secrets, so a follower's credentials can't flush the caches or switch the controlling CL
+pub enum AdminAuth {
+    Token(Vec<u8>),
+    Jwt(DecodingKey),
+}
+
+impl AdminAuth {
+    // a token is the file's first line, a secret is in the format the engine API's are
+    pub fn load(secret: &AdminSecret) -> Result<AdminAuth, String> {
+        match secret {
+            AdminSecret::Token(path) => {
+                let file = std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+                match file.lines().next().map(str::trim).unwrap_or_default() {
+                    "" => Err(format!("{} has no token in it", path)),
+                    v => Ok(AdminAuth::Token(v.as_bytes().to_vec())),
+                }
+            }
+            AdminSecret::Jwt(path) => Ok(AdminAuth::Jwt(DecodingKey::from_secret(&read_secret(path)?))),
+        }
+    }
+
+    // the token is compared in full whatever it starts with, so how long a wrong one took doesn't tell how close it was
+    pub fn check(&self, token: &str, max_skew: u64) -> Result<(), AuthError> {
+        match self {
+            AdminAuth::Token(expected) => {
+                let token = token.as_bytes();
+                let diff = expected.iter().zip(token.iter().cycle()).fold(0, |acc, (a, b)| acc | (a ^ b));
+                match diff == 0 && token.len() == expected.len() {
+                    true => Ok(()),
+                    false => Err(AuthError::WrongToken),
+                }
+            }
+            AdminAuth::Jwt(key) => verify(token, key, max_skew).map(|_| ()),
+        }
+    }
+}
+
+// how often the secret files are checked for changes, a SIGHUP checks them right away
+const SECRETS_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[inline(always)]
+pub fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
+// whether the wait ended with a SIGHUP, which reloads even if nothing looks changed
+#[cfg(unix)]
+pub async fn wait_for_check(interval: &mut tokio::time::Interval, hangup: &mut Option<tokio::signal::unix::Signal>) -> bool {
+    match hangup {
+        Some(hangup) => tokio::select! {
+            _ = interval.tick() => false,
+            _ = hangup.recv() => true,
+        },
+        None => {
+            interval.tick().await;
+            false
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn wait_for_check(interval: &mut tokio::time::Interval, _hangup: &mut Option<()>) -> bool {
+    interval.tick().await;
+    false
+}
+
+// swaps in the secrets whenever their files change, so they can be rotated without a restart.
+// a file that doesn't load keeps the secrets from before it, an operator halfway through an edit shouldn't lock everyone out
+pub fn spawn_secrets_reloader(
+    canonical_path: String,
+    el_path: String,
+    jwt_keys: Arc<RwLock<JwtKeys>>,
+    clients: Option<(String, Arc<RwLock<ClientSecrets>>)>,
+    admin: Option<(AdminSecret, Arc<RwLock<AdminAuth>>)>,
+) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+        #[cfg(not(unix))]
+        let mut hangup = None;
+
+        let mut interval = tokio::time::interval(SECRETS_POLL_INTERVAL);
+        let mut jwt_modified = (modified(&canonical_path), modified(&el_path));
+        let mut clients_modified = clients.as_ref().and_then(|(path, _)| modified(path));
+        let mut admin_modified = admin.as_ref().and_then(|(secret, _)| modified(secret.path()));
+        loop {
+            let forced = wait_for_check(&mut interval, &mut hangup).await;
+
+            let now_modified = (modified(&canonical_path), modified(&el_path));
+            if forced || now_modified != jwt_modified {
+                jwt_modified = now_modified;
+                match JwtKeys::load(&canonical_path, &el_path) {
+                    Ok(v) => {
+                        *jwt_keys.write().await = v;
+                        tracing::info!("Reloaded JWT secrets");
+                    }
+                    Err(e) => tracing::error!("Unable to reload JWT secrets, keeping the old ones: {}", e),
+                }
+            }
+
+            if let Some((path, secrets)) = &clients {
+                let now_modified = modified(path);
+                if forced || now_modified != clients_modified {
+                    clients_modified = now_modified;
+                    match ClientSecrets::load(path) {
+                        Ok(v) => {
+                            tracing::info!("Reloaded JWT secrets of {} followers", v.len());
+                            *secrets.write().await = v;
+                        }
+                        Err(e) => tracing::error!("Unable to reload follower JWT secrets, keeping the old ones: {}", e),
+                    }
+                }
+            }
+
+            if let Some((secret, auth)) = &admin {
+                let now_modified = modified(secret.path());
+                if forced || now_modified != admin_modified {
+                    admin_modified = now_modified;
+                    match AdminAuth::load(secret) {
+                        Ok(v) => {
+                            *auth.write().await = v;
+                            tracing::info!("Reloaded the admin API's secret");
+                        }
+                        Err(e) => tracing::error!("Unable to reload the admin API's secret, keeping the old one: {}", e),
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,136 @@
+// followers an admin turned away, by name or by the address or network they connect from, for when a CL that was
+// set up wrong floods OE. kept in the db, so a restart doesn't let one back in before its ban is up
+use crate::storage::{Storage, StorageError};
+use chrono::{DateTime, Utc};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, net::IpAddr, sync::Mutex, time::Duration};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    pub since: DateTime<Utc>,
+    pub until: Option<DateTime<Utc>>, // None until an admin lifts it
+    pub reason: Option<String>,
+}
+
+impl Ban {
+    #[inline(always)]
+    fn is_over(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_some_and(|v| v <= now)
+    }
+
+    // what the banned follower is told
+    pub fn message(&self) -> String {
+        let mut message = match self.until {
+            Some(v) => format!("Banned by an admin until {}", v.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+            None => "Banned by an admin".to_owned(),
+        };
+        if let Some(v) = &self.reason {
+            message = format!("{}: {}", message, v);
+        }
+        message
+    }
+}
+
+struct Entry {
+    ban: Ban,
+    net: Option<IpNet>, // when it's an address or a network rather than a name
+}
+
+pub struct Bans {
+    bans: Mutex<HashMap<String, Entry>>, // by what the admin gave, a follower name, an address or a network
+}
+
+#[inline(always)]
+fn entry(client: &str, ban: Ban) -> Entry {
+    Entry {
+        net: crate::access::parse_net(client).ok(),
+        ban,
+    }
+}
+
+impl Bans {
+    // the bans as they were written to the db, the ones that are up by now are dropped from it
+    pub async fn restore(db: &dyn Storage) -> Bans {
+        let mut restored = HashMap::new();
+        let records = match db.get_bans().await {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::error!("Unable to get the bans from db: {}", e);
+                HashMap::new()
+            }
+        };
+        let now = Utc::now();
+        for (client, record) in records {
+            match serde_json::from_str::<Ban>(&record) {
+                Ok(v) if v.is_over(now) => {
+                    if let Err(e) = db.remove_ban(&client).await {
+                        tracing::error!("Unable to remove the ban of {} from db: {}", client, e);
+                    }
+                }
+                Ok(v) => {
+                    let entry = entry(&client, v);
+                    restored.insert(client, entry);
+                }
+                Err(e) => tracing::error!("Unable to parse the ban of {} from db: {}", client, e),
+            }
+        }
+        if !restored.is_empty() {
+            tracing::info!("Restored {} bans", restored.len());
+        }
+        Bans {
+            bans: Mutex::new(restored),
+        }
+    }
+
+    // client is a follower's name, or an address or network its requests come from. for duration, or until
+    // it's lifted without one
+    pub async fn ban(&self, db: &dyn Storage, client: &str, duration: Option<Duration>, reason: Option<String>) -> Result<Ban, StorageError> {
+        let now = Utc::now();
+        let ban = Ban {
+            since: now,
+            until: duration.map(|v| now + chrono::Duration::from_std(v).unwrap_or(chrono::Duration::MAX)),
+            reason,
+        };
+        let record = serde_json::to_string(&ban).map_err(|e| StorageError(e.to_string()))?;
+        db.put_ban(client, &record).await?;
+        self.bans.lock().unwrap().insert(client.to_owned(), entry(client, ban.clone()));
+        Ok(ban)
+    }
+
+    // whether there was a ban to lift
+    pub async fn unban(&self, db: &dyn Storage, client: &str) -> Result<bool, StorageError> {
+        db.remove_ban(client).await?;
+        Ok(self.bans.lock().unwrap().remove(client).is_some())
+    }
+
+    #[inline(always)]
+    pub fn by_name(&self, name: &str) -> Option<Ban> {
+        let bans = self.bans.lock().unwrap();
+        bans.get(name).map(|v| &v.ban).filter(|v| !v.is_over(Utc::now())).cloned()
+    }
+
+    // a v4 peer on a dual-stack listener is matched as its v4 address, like --client-deny does
+    #[inline(always)]
+    pub fn by_address(&self, ip: IpAddr) -> Option<Ban> {
+        let ip = ip.to_canonical();
+        let now = Utc::now();
+        let bans = self.bans.lock().unwrap();
+        bans.values()
+            .find(|v| v.net.is_some_and(|net| net.contains(&ip)) && !v.ban.is_over(now))
+            .map(|v| v.ban.clone())
+    }
+
+    // the ones still in force, by what they're on
+    pub fn all(&self) -> Vec<(String, Ban)> {
+        let now = Utc::now();
+        let bans = self.bans.lock().unwrap();
+        let mut all: Vec<_> = bans
+            .iter()
+            .filter(|(_, v)| !v.ban.is_over(now))
+            .map(|(k, v)| (k.clone(), v.ban.clone()))
+            .collect();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        all
+    }
+}
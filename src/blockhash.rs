@@ -0,0 +1,266 @@
+// recomputes the block hash of an execution payload, the keccak of its RLP encoded header
+// a payload whose blockHash doesn't match can be answered with INVALID_BLOCK_HASH without asking the EL.
+// also turns a payload into the block eth_getBlockByHash would answer with, for the followers' tooling
+use crate::primitives::{Address, Bloom, Bytes, H256, U256};
+use crate::types::{ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3, WithdrawalV1};
+use keccak_hasher::KeccakHasher;
+use rlp::RlpStream;
+use sha3::{Digest, Keccak256};
+
+// keccak of the RLP of an empty list, post merge blocks never have ommers
+const EMPTY_OMMERS_HASH: [u8; 32] = [
+    0x1d, 0xcc, 0x4d, 0xe8, 0xde, 0xc7, 0x5d, 0x7a, 0xab, 0x85, 0xb5, 0x67, 0xb6, 0xcc, 0xd4, 0x1a, 0xd3, 0x12,
+    0x45, 0x1b, 0x94, 0x8a, 0x74, 0x13, 0xf0, 0xa1, 0x42, 0xfd, 0x40, 0xd4, 0x93, 0x47,
+];
+
+// the payload fields that go into the header, borrowed from whichever payload version it is
+struct Header<'a> {
+    parent_hash: &'a H256,
+    fee_recipient: &'a Address,
+    state_root: &'a H256,
+    receipts_root: &'a H256,
+    logs_bloom: &'a Bloom,
+    prev_randao: &'a H256,
+    block_number: u64,
+    gas_limit: u64,
+    gas_used: u64,
+    timestamp: u64,
+    extra_data: &'a Bytes,
+    base_fee_per_gas: &'a U256,
+    transactions: &'a [Bytes],
+    // the fields below only exist from the fork that added them
+    withdrawals: Option<&'a [WithdrawalV1]>,
+    blob_gas_used: Option<u64>,
+    excess_blob_gas: Option<u64>,
+    parent_beacon_block_root: Option<&'a H256>,
+    requests_hash: Option<&'a H256>,
+}
+
+#[inline(always)]
+fn transactions_root(transactions: &[Bytes]) -> Result<[u8; 32], String> {
+    // typed transactions are already their envelope, so the bytes go into the trie as they are
+    let transactions = transactions.iter().map(|v| v.to_vec()).collect::<Result<Vec<_>, _>>()?;
+    Ok(triehash::ordered_trie_root::<KeccakHasher, _>(transactions))
+}
+
+#[inline(always)]
+fn withdrawal_rlp(w: &WithdrawalV1) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(4);
+    stream.append(&w.index.as_u64());
+    stream.append(&w.validatorIndex.as_u64());
+    stream.append(&w.address.0);
+    stream.append(&w.amount.as_u64());
+    stream.out().to_vec()
+}
+
+#[inline(always)]
+fn withdrawals_root(withdrawals: &[WithdrawalV1]) -> [u8; 32] {
+    triehash::ordered_trie_root::<KeccakHasher, _>(withdrawals.iter().map(withdrawal_rlp))
+}
+
+#[inline(always)]
+fn header_rlp(header: &Header) -> Result<Vec<u8>, String> {
+    let mut stream = RlpStream::new();
+    stream.begin_unbounded_list();
+    stream.append(&header.parent_hash.0);
+    stream.append(&EMPTY_OMMERS_HASH.as_slice());
+    stream.append(&header.fee_recipient.0);
+    stream.append(&header.state_root.0);
+    stream.append(&transactions_root(header.transactions)?.as_slice());
+    stream.append(&header.receipts_root.0);
+    stream.append(&header.logs_bloom.0);
+    // difficulty is zero after the merge
+    stream.append(&0u64);
+    stream.append(&header.block_number);
+    stream.append(&header.gas_limit);
+    stream.append(&header.gas_used);
+    stream.append(&header.timestamp);
+    stream.append(&header.extra_data.to_vec()?);
+    stream.append(&header.prev_randao.0);
+    // so is the nonce, which is always 8 bytes
+    stream.append(&[0u8; 8].as_slice());
+    stream.append(&header.base_fee_per_gas.0);
+
+    // every fork appends its fields, so a field is only present if all the ones before it are
+    if let Some(withdrawals) = header.withdrawals {
+        stream.append(&withdrawals_root(withdrawals).as_slice());
+    }
+    if let (Some(blob_gas_used), Some(excess_blob_gas)) = (header.blob_gas_used, header.excess_blob_gas) {
+        stream.append(&blob_gas_used);
+        stream.append(&excess_blob_gas);
+    }
+    if let Some(parent_beacon_block_root) = header.parent_beacon_block_root {
+        stream.append(&parent_beacon_block_root.0);
+    }
+    if let Some(requests_hash) = header.requests_hash {
+        stream.append(&requests_hash.0);
+    }
+    stream.finalize_unbounded_list();
+    Ok(stream.out().to_vec())
+}
+
+#[inline(always)]
+fn header_hash(header: Header) -> Result<H256, String> {
+    Ok(H256(ethereum_types::H256::from_slice(&Keccak256::digest(header_rlp(&header)?))))
+}
+
+#[inline(always)]
+fn hex_quantity(v: u64) -> String {
+    format!("0x{:x}", v)
+}
+
+// the block the way the EL's eth_getBlockByHash answers with it, the transactions by their hashes
+fn block_json(header: Header, hash: &H256) -> Result<serde_json::Value, String> {
+    let transactions = header.transactions.iter().map(|v| v.to_vec()).collect::<Result<Vec<_>, _>>()?;
+    let header_rlp = header_rlp(&header)?;
+
+    // the size is that of the whole RLP encoded block, a legacy transaction is already a list and a typed one
+    // goes in as a string
+    let mut stream = RlpStream::new();
+    stream.begin_unbounded_list();
+    stream.append_raw(&header_rlp, 1);
+    stream.begin_list(transactions.len());
+    for tx in &transactions {
+        match tx.first() {
+            Some(v) if *v >= 0xc0 => stream.append_raw(tx, 1),
+            _ => stream.append(tx),
+        };
+    }
+    stream.begin_list(0);
+    if let Some(withdrawals) = header.withdrawals {
+        stream.begin_list(withdrawals.len());
+        for w in withdrawals {
+            stream.append_raw(&withdrawal_rlp(w), 1);
+        }
+    }
+    stream.finalize_unbounded_list();
+
+    let mut block = serde_json::json!({
+        "hash": hash,
+        "parentHash": header.parent_hash,
+        "sha3Uncles": format!("0x{}", hex::encode(EMPTY_OMMERS_HASH)),
+        "miner": header.fee_recipient,
+        "stateRoot": header.state_root,
+        "transactionsRoot": format!("0x{}", hex::encode(triehash::ordered_trie_root::<KeccakHasher, _>(&transactions))),
+        "receiptsRoot": header.receipts_root,
+        "logsBloom": header.logs_bloom,
+        "difficulty": "0x0",
+        "number": hex_quantity(header.block_number),
+        "gasLimit": hex_quantity(header.gas_limit),
+        "gasUsed": hex_quantity(header.gas_used),
+        "timestamp": hex_quantity(header.timestamp),
+        "extraData": header.extra_data,
+        "mixHash": header.prev_randao,
+        "nonce": "0x0000000000000000",
+        "baseFeePerGas": header.base_fee_per_gas,
+        "size": hex_quantity(stream.out().len() as u64),
+        "transactions": transactions.iter().map(|v| format!("0x{}", hex::encode(Keccak256::digest(v)))).collect::<Vec<_>>(),
+        "uncles": [],
+    });
+    if let Some(withdrawals) = header.withdrawals {
+        block["withdrawals"] = serde_json::json!(withdrawals);
+        block["withdrawalsRoot"] = format!("0x{}", hex::encode(withdrawals_root(withdrawals))).into();
+    }
+    if let (Some(blob_gas_used), Some(excess_blob_gas)) = (header.blob_gas_used, header.excess_blob_gas) {
+        block["blobGasUsed"] = hex_quantity(blob_gas_used).into();
+        block["excessBlobGas"] = hex_quantity(excess_blob_gas).into();
+    }
+    if let Some(v) = header.parent_beacon_block_root {
+        block["parentBeaconBlockRoot"] = serde_json::json!(v);
+    }
+    if let Some(v) = header.requests_hash {
+        block["requestsHash"] = serde_json::json!(v);
+    }
+    Ok(block)
+}
+
+// the header fields every payload version has, the later forks' fields are filled in by the caller
+macro_rules! common_header {
+    ($payload:expr) => {
+        Header {
+            parent_hash: &$payload.parentHash,
+            fee_recipient: &$payload.feeRecipient,
+            state_root: &$payload.stateRoot,
+            receipts_root: &$payload.receiptsRoot,
+            logs_bloom: &$payload.logsBloom,
+            prev_randao: &$payload.prevRandao,
+            block_number: $payload.blockNumber.as_u64(),
+            gas_limit: $payload.gasLimit.as_u64(),
+            gas_used: $payload.gasUsed.as_u64(),
+            timestamp: $payload.timestamp.as_u64(),
+            extra_data: &$payload.extraData,
+            base_fee_per_gas: &$payload.baseFeePerGas,
+            transactions: &$payload.transactions,
+            withdrawals: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            parent_beacon_block_root: None,
+            requests_hash: None,
+        }
+    };
+}
+
+#[inline(always)]
+pub fn payload_v1_block_hash(payload: &ExecutionPayloadV1) -> Result<H256, String> {
+    header_hash(common_header!(payload))
+}
+
+#[inline(always)]
+pub fn payload_v2_block_hash(payload: &ExecutionPayloadV2) -> Result<H256, String> {
+    header_hash(Header {
+        withdrawals: Some(&payload.withdrawals),
+        ..common_header!(payload)
+    })
+}
+
+// the parent beacon block root and the requests hash come from the newPayload params
+#[inline(always)]
+pub fn payload_v3_block_hash(
+    payload: &ExecutionPayloadV3,
+    parent_beacon_block_root: &H256,
+    requests_hash: Option<&H256>,
+) -> Result<H256, String> {
+    header_hash(Header {
+        withdrawals: Some(&payload.withdrawals),
+        blob_gas_used: Some(payload.blobGasUsed.as_u64()),
+        excess_blob_gas: Some(payload.excessBlobGas.as_u64()),
+        parent_beacon_block_root: Some(parent_beacon_block_root),
+        requests_hash,
+        ..common_header!(payload)
+    })
+}
+
+#[inline(always)]
+pub fn payload_v1_block(payload: &ExecutionPayloadV1) -> Result<serde_json::Value, String> {
+    block_json(common_header!(payload), &payload.blockHash)
+}
+
+#[inline(always)]
+pub fn payload_v2_block(payload: &ExecutionPayloadV2) -> Result<serde_json::Value, String> {
+    block_json(
+        Header {
+            withdrawals: Some(&payload.withdrawals),
+            ..common_header!(payload)
+        },
+        &payload.blockHash,
+    )
+}
+
+#[inline(always)]
+pub fn payload_v3_block(
+    payload: &ExecutionPayloadV3,
+    parent_beacon_block_root: &H256,
+    requests_hash: Option<&H256>,
+) -> Result<serde_json::Value, String> {
+    block_json(
+        Header {
+            withdrawals: Some(&payload.withdrawals),
+            blob_gas_used: Some(payload.blobGasUsed.as_u64()),
+            excess_blob_gas: Some(payload.excessBlobGas.as_u64()),
+            parent_beacon_block_root: Some(parent_beacon_block_root),
+            requests_hash,
+            ..common_header!(payload)
+        },
+        &payload.blockHash,
+    )
+}
@@ -0,0 +1,91 @@
+// the blocks of the last valid payloads, in the shape eth_getBlockByHash answers with, so the followers' tooling
+// can be answered about recent blocks without the EL. the newPayloads they came in are kept with them, for
+// POST /admin/replay to bring another EL up to head with
+use crate::primitives::H256;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+// a few minutes of blocks, full blocks are large and older ones are asked for far less
+const RECENT_PAYLOADS: usize = 64;
+
+struct Block {
+    number: u64,
+    parent: H256,
+    json: Arc<serde_json::Value>,
+    request: Arc<str>, // the newPayload as the CL sent it
+}
+
+#[derive(Default)]
+pub struct RecentPayloads {
+    blocks: Mutex<(HashMap<H256, Block>, VecDeque<H256>)>, // by hash, and the hashes oldest first
+}
+
+impl RecentPayloads {
+    pub fn insert(&self, hash: H256, number: u64, parent: H256, json: serde_json::Value, request: &str) {
+        let mut blocks = self.blocks.lock().unwrap();
+        let (by_hash, order) = &mut *blocks;
+        if by_hash.contains_key(&hash) {
+            return;
+        }
+        order.push_back(hash);
+        if order.len() > RECENT_PAYLOADS {
+            if let Some(v) = order.pop_front() {
+                by_hash.remove(&v);
+            }
+        }
+        by_hash.insert(
+            hash,
+            Block {
+                number,
+                parent,
+                json: Arc::new(json),
+                request: Arc::from(request),
+            },
+        );
+    }
+
+    #[inline(always)]
+    pub fn by_hash(&self, hash: &H256) -> Option<Arc<serde_json::Value>> {
+        self.blocks.lock().unwrap().0.get(hash).map(|v| v.json.clone())
+    }
+
+    // the block at number on head's branch, None when it's past head or older than what's kept
+    pub fn by_number(&self, head: &H256, number: u64) -> Option<Arc<serde_json::Value>> {
+        let blocks = self.blocks.lock().unwrap();
+        let mut block = blocks.0.get(head)?;
+        while block.number > number {
+            block = blocks.0.get(&block.parent)?;
+        }
+        (block.number == number).then(|| block.json.clone())
+    }
+
+    // the newPayloads of the blocks numbered from to to on head's branch, oldest first, as far back as they're kept
+    pub fn requests(&self, head: &H256, from: u64, to: u64) -> Vec<(u64, H256, Arc<str>)> {
+        let blocks = self.blocks.lock().unwrap();
+        let mut requests = Vec::new();
+        let mut hash = *head;
+        while let Some(block) = blocks.0.get(&hash) {
+            if block.number < from {
+                break;
+            }
+            if block.number <= to {
+                requests.push((block.number, hash, block.request.clone()));
+            }
+            hash = block.parent;
+        }
+        requests.reverse();
+        requests
+    }
+
+    // every block, or the ones numbered from to to, returns how many there were
+    pub fn remove(&self, range: Option<(u64, u64)>) -> u64 {
+        let mut blocks = self.blocks.lock().unwrap();
+        let (by_hash, order) = &mut *blocks;
+        let before = by_hash.len();
+        by_hash.retain(|_, v| range.is_some_and(|(from, to)| v.number < from || v.number > to));
+        order.retain(|v| by_hash.contains_key(v));
+        (before - by_hash.len()) as u64
+    }
+}
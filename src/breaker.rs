@@ -0,0 +1,92 @@
+// a circuit breaker for each EL node, so followers don't each wait out a connect and a timeout while it's down
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Debug)]
+struct BreakerState {
+    failures: u32,     // calls in a row that didn't get an answer
+    probe_at: Instant, // when an open breaker lets the next call through to see if the node is back
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32, // failures in a row that open it, 0 never does
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+    recovered: AtomicBool, // answered after calls it didn't, like once it's been restarted
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            state: Mutex::new(BreakerState {
+                failures: 0,
+                probe_at: Instant::now(),
+            }),
+            recovered: AtomicBool::new(false),
+        }
+    }
+
+    #[inline(always)]
+    fn tripped(&self, state: &BreakerState) -> bool {
+        self.threshold > 0 && state.failures >= self.threshold
+    }
+
+    #[inline(always)]
+    pub fn is_open(&self) -> bool {
+        self.tripped(&self.state.lock().unwrap())
+    }
+
+    // whether a follower's call may go to the node. an open breaker lets one through every cooldown,
+    // whatever that call gets back decides if it closes
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !self.tripped(&state) {
+            return true;
+        }
+        let now = Instant::now();
+        if now < state.probe_at {
+            return false;
+        }
+        state.probe_at = now + self.cooldown;
+        true
+    }
+
+    // every call to the node, a follower's or the canonical CL's, counts towards the breaker
+    pub fn record(&self, answered: bool, url: &str) {
+        let mut state = self.state.lock().unwrap();
+        if answered {
+            if self.tripped(&state) {
+                tracing::info!("{} is answering again, closing its circuit breaker", url);
+            }
+            if state.failures > 0 {
+                self.recovered.store(true, Ordering::Relaxed);
+            }
+            state.failures = 0;
+            return;
+        }
+
+        state.failures = state.failures.saturating_add(1);
+        if self.threshold > 0 && state.failures == self.threshold {
+            state.probe_at = Instant::now() + self.cooldown;
+            tracing::warn!(
+                "{} failed {} calls in a row, answering followers without it for the next {}s",
+                url,
+                state.failures,
+                self.cooldown.as_secs()
+            );
+        }
+    }
+    // whether the node answered again after failing since this was last asked
+    #[inline(always)]
+    pub fn take_recovered(&self) -> bool {
+        self.recovered.swap(false, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,40 @@
+// which EL nodes answered eth_chainId with the chain --chain-id or --network says, so an EL on another network
+// never serves this one's CLs, even when it couldn't be asked at startup
+use std::{collections::HashSet, sync::RwLock};
+
+pub struct ChainCheck {
+    pub expected: u64,
+    checked: RwLock<HashSet<String>>, // by url
+    wrong: RwLock<Option<String>>,    // the node on another chain, once there's one OE stops serving
+}
+
+impl ChainCheck {
+    pub fn new(expected: u64) -> ChainCheck {
+        ChainCheck {
+            expected,
+            checked: RwLock::new(HashSet::new()),
+            wrong: RwLock::new(None),
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_checked(&self, url: &str) -> bool {
+        self.checked.read().unwrap().contains(url)
+    }
+
+    // what a node answered, the error to stop serving with when it's another chain
+    pub fn answered(&self, url: &str, chain_id: u64) -> Result<(), String> {
+        if chain_id == self.expected {
+            self.checked.write().unwrap().insert(url.to_owned());
+            return Ok(());
+        }
+        let e = format!("{} is on chain {}, not {}", url, chain_id, self.expected);
+        self.wrong.write().unwrap().get_or_insert_with(|| e.clone());
+        Err(e)
+    }
+
+    #[inline(always)]
+    pub fn wrong(&self) -> Option<String> {
+        self.wrong.read().unwrap().clone()
+    }
+}
@@ -0,0 +1,204 @@
+// the check-config subcommand, a dry run of what openexecution needs at startup for provisioning pipelines to run
+// before a deploy. every check runs even when an earlier one failed, so one run tells everything that's wrong
+use crate::{auth, nodes, storage, types::Node};
+use serde::Serialize;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::RwLock;
+
+// what the engine API spec asks of the secret the CL and the EL share
+const JWT_SECRET_BYTES: usize = 32;
+
+// how long the db gets to connect, redis keeps retrying one that isn't there
+const DB_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize)]
+struct Check {
+    check: String,
+    ok: bool,
+    detail: String,
+}
+
+#[derive(Default, Serialize)]
+struct Report {
+    ok: bool,
+    checks: Vec<Check>,
+}
+
+impl Report {
+    fn add(&mut self, check: &str, result: Result<String, String>) {
+        let (ok, detail) = match result {
+            Ok(v) => (true, v),
+            Err(e) => (false, e),
+        };
+        self.checks.push(Check {
+            check: check.to_owned(),
+            ok,
+            detail,
+        });
+    }
+}
+
+#[inline(always)]
+fn secret(path: &str) -> Result<String, String> {
+    match auth::read_secret(path)?.len() {
+        JWT_SECRET_BYTES => Ok(format!("{}, {} bytes", path, JWT_SECRET_BYTES)),
+        v => Err(format!("{} has {} bytes, the engine API wants {}", path, v, JWT_SECRET_BYTES)),
+    }
+}
+
+async fn db(matches: &clap::ArgMatches<'_>) -> Result<String, String> {
+    let backend = match matches.is_present("no-db") {
+        true => "memory",
+        false => matches.value_of("db-backend").unwrap(),
+    };
+    let db: Box<dyn storage::Storage> = match backend {
+        // a dry run leaves the schema as it is
+        "postgres" => Box::new(storage::PostgresStorage::connect(&crate::postgres_options(matches, false)?).await.map_err(|e| e.to_string())?),
+        "redis" => Box::new(storage::RedisStorage::connect(matches.value_of("redis-url").unwrap(), None).await.map_err(|e| e.to_string())?),
+        // sled makes the directory when it isn't there, a dry run doesn't
+        "sled" => match matches.value_of("sled-path").unwrap() {
+            path if !std::path::Path::new(path).exists() => return Ok(format!("sled, {} is made at startup", path)),
+            path => Box::new(storage::SledStorage::open(path, 0).map_err(|e| format!("Unable to open {}: {}", path, e))?),
+        },
+        _ => return Ok("memory, nothing to connect to".to_owned()),
+    };
+    db.ping().await.map_err(|e| e.to_string())?;
+    Ok(format!("connected to {}", backend))
+}
+
+// whether an EL node answers, takes the JWT and is on the right chain
+async fn node(node: &Arc<Node>, jwt_keys: Option<&RwLock<auth::JwtKeys>>, chain_id: Option<u64>) -> Result<String, String> {
+    let mut detail = Vec::new();
+    if let Some(jwt_keys) = jwt_keys {
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"engine_exchangeCapabilities","params":[[]]}"#.to_owned();
+        let resp = crate::make_auth_request(jwt_keys, node, "engine_exchangeCapabilities", body).await.map_err(|e| format!("{}: {}", node.url, e))?;
+        match serde_json::from_str::<serde_json::Value>(&resp) {
+            Ok(v) if v["result"].is_array() => detail.push("takes the JWT".to_owned()),
+            _ => return Err(format!("{} didn't answer engine_exchangeCapabilities: {}", node.url, resp)),
+        }
+    }
+    let answered = crate::fetch_chain_id(jwt_keys, node).await.map_err(|e| format!("{}: {}", node.url, e))?;
+    match chain_id {
+        Some(v) if v != answered => return Err(format!("{} is on chain {}, not {}", node.url, answered, v)),
+        _ => detail.push(format!("on chain {}", answered)),
+    }
+    Ok(format!("{} {}", node.url, detail.join(", ")))
+}
+
+// runs the checks and prints how each went, as text or as one json object. whether they all passed
+pub async fn run(matches: &clap::ArgMatches<'_>, config_path: Option<&str>, chain_id: Option<u64>, json: bool) -> bool {
+    let mut report = Report::default();
+
+    // a file that doesn't parse never gets this far, it stops openexecution with its line and column
+    report.add(
+        "config",
+        Ok(match config_path {
+            Some(v) => format!("{} parsed", v),
+            None => "no --config, only the command line and the environment".to_owned(),
+        }),
+    );
+
+    let no_unauth = matches.is_present("no-unauth");
+    let required = [("node", true), ("jwt-secret", true), ("unauth-node", !no_unauth), ("client-jwt-secrets", no_unauth)];
+    let missing: Vec<_> = required.iter().filter(|(name, needed)| *needed && !matches.is_present(name)).map(|(name, _)| format!("--{}", name)).collect();
+    report.add(
+        "settings",
+        match missing.is_empty() {
+            true => Ok("everything required is set".to_owned()),
+            false => Err(format!("missing {}", missing.join(", "))),
+        },
+    );
+
+    let jwt_secret = matches.value_of("jwt-secret");
+    let el_jwt_secret = matches.value_of("el-jwt-secret").or(jwt_secret);
+    if let Some(path) = jwt_secret {
+        report.add("jwt-secret", secret(path));
+    }
+    if let Some(path) = el_jwt_secret.filter(|v| Some(*v) != jwt_secret) {
+        report.add("el-jwt-secret", secret(path));
+    }
+    if let Some(path) = matches.value_of("client-jwt-secrets") {
+        report.add("client-jwt-secrets", auth::ClientSecrets::load(path).map(|_| format!("{} loaded", path)));
+    }
+    if let Some(path) = matches.value_of("admin-token") {
+        let token = auth::AdminAuth::load(&auth::AdminSecret::Token(path.to_owned()));
+        report.add("admin-token", token.map(|_| format!("{} loaded", path)));
+    }
+    if let Some(path) = matches.value_of("admin-jwt-secret") {
+        report.add("admin-jwt-secret", secret(path));
+    }
+
+    let mut overrides = std::collections::HashMap::new();
+    for v in matches.values_of("node-settings").into_iter().flatten().filter(|v| !v.is_empty()) {
+        match nodes::NodeOverrides::parse(v) {
+            Ok((url, v)) => {
+                if let Some(path) = &v.jwt_secret {
+                    report.add(&format!("jwt-secret {}", url), secret(path));
+                }
+                overrides.insert(url, v);
+            }
+            Err(e) => report.add("node-settings", Err(e)),
+        }
+    }
+
+    let db = match tokio::time::timeout(DB_TIMEOUT, db(matches)).await {
+        Ok(v) => v,
+        Err(_) => Err(format!("didn't connect within {}s", DB_TIMEOUT.as_secs())),
+    };
+    report.add("db", db);
+
+    let jwt_keys = match (jwt_secret, el_jwt_secret) {
+        (Some(canonical), Some(el)) => auth::JwtKeys::load(canonical, el).ok().map(RwLock::new),
+        _ => None,
+    };
+    let settings = nodes::NodeSettings {
+        client: crate::types::NodeClientOptions {
+            pool_size: None,
+            idle_timeout: None,
+            tcp_keepalive: None,
+            http2: matches.is_present("node-http2"),
+            nodelay: true,
+            compression: false,
+        },
+        upstream: crate::types::UpstreamPolicy {
+            other: Duration::from_secs(matches.value_of("node-timeout").and_then(|v| v.parse().ok()).unwrap_or(30)),
+            retries: 0,
+        },
+        overrides,
+        breaker_threshold: 0,
+        breaker_cooldown: Duration::from_secs(1),
+        metrics: Arc::new(crate::metrics::Metrics::new()),
+    };
+    let auth_nodes = matches
+        .value_of("node")
+        .map(|v| ("auth".to_owned(), v))
+        .into_iter()
+        .chain(matches.values_of("backup-nodes").into_iter().flatten().enumerate().map(|(i, v)| (format!("backup-{}", i + 1), v)))
+        .chain(matches.values_of("verify-nodes").into_iter().flatten().enumerate().map(|(i, v)| (format!("verify-{}", i + 1), v)));
+    for (name, url) in auth_nodes {
+        let result = match (settings.node(&name, url), &jwt_keys) {
+            (Ok(v), Some(jwt_keys)) => node(&v, Some(jwt_keys), chain_id).await,
+            (Ok(_), None) => Err("no JWT secret to sign with".to_owned()),
+            (Err(e), _) => Err(format!("Unable to set up the client for {}: {}", url, e)),
+        };
+        report.add(&format!("el {}", name), result);
+    }
+    if let Some(url) = matches.value_of("unauth-node") {
+        let result = match settings.node("unauth", url) {
+            Ok(v) => node(&v, None, chain_id).await,
+            Err(e) => Err(format!("Unable to set up the client for {}: {}", url, e)),
+        };
+        report.add("el unauth", result);
+    }
+
+    report.ok = report.checks.iter().all(|v| v.ok);
+    match json {
+        true => println!("{}", serde_json::to_string(&report).unwrap()),
+        false => {
+            for check in &report.checks {
+                println!("{:<4}  {:<24}  {}", if check.ok { "ok" } else { "FAIL" }, check.check, check.detail);
+            }
+        }
+    }
+    report.ok
+}
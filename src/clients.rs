@@ -0,0 +1,245 @@
+// every follower openexecution has answered, who it is and how it's doing, kept in the db so the roster
+// survives a restart. the controlling CL's is kept too, for this run alone
+use crate::{storage::Storage, types::ForkchoiceStateV1};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+// how a follower was told apart from the others
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Identity {
+    Jwt,         // its name in --client-jwt-secrets
+    Certificate, // a name in its client certificate
+    Address,     // nothing better, its ip
+    Ipc,         // the unix socket, they all share it
+}
+
+// a follower within this many blocks of the controlling CL's head is following it, the controlling CL's fcU and
+// a follower's can arrive in either order
+const SYNCED_LAG: u64 = 2;
+
+// where a follower is against the controlling CL's head, by the fcUs it sends
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SyncState {
+    Synced, // its head is the controlling CL's, or about to be
+    #[default]
+    Syncing, // behind, but its head moves
+    Stalled, // behind, and its head hasn't moved in the stall timeout
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Client {
+    pub identity: Identity,
+    pub clv: Option<String>, // the client version its jwt claims
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub forkchoice: Option<ForkchoiceStateV1>, // from its last fcU
+    #[serde(default)]
+    pub sync: SyncState,
+    #[serde(default = "Utc::now")]
+    pub head_since: DateTime<Utc>, // when its head last changed
+    pub requests: u64,
+    pub errors: u64, // requests it got a JSON-RPC error for
+}
+
+impl Client {
+    fn new(identity: Identity) -> Client {
+        let now = Utc::now();
+        Client {
+            identity,
+            clv: None,
+            first_seen: now,
+            last_seen: now,
+            forkchoice: None,
+            sync: SyncState::default(),
+            head_since: now,
+            requests: 0,
+            errors: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Roster {
+    clients: HashMap<String, Client>, // by name, or address, like the metrics
+    dirty: HashSet<String>,           // changed since they were last written to the db
+}
+
+pub struct ClientRegistry {
+    roster: Mutex<Roster>,
+    canonical: Mutex<Option<Client>>, // None until it's sent something
+    stall_after: chrono::Duration, // how long a follower that's behind can keep the same head before it's stalled
+}
+
+impl ClientRegistry {
+    // the roster as it was written to the db, an unreadable one starts empty
+    pub async fn restore(db: &dyn Storage, stall_after: Duration) -> ClientRegistry {
+        let mut roster = Roster::default();
+        match db.get_clients().await {
+            Ok(v) => {
+                for (name, record) in v {
+                    match serde_json::from_str::<Client>(&record) {
+                        Ok(client) => {
+                            roster.clients.insert(name, client);
+                        }
+                        Err(e) => tracing::error!("Unable to parse follower {} from db: {}", name, e),
+                    }
+                }
+                if !roster.clients.is_empty() {
+                    tracing::info!("Restored {} followers", roster.clients.len());
+                }
+            }
+            Err(e) => tracing::error!("Unable to get the followers from db: {}", e),
+        }
+        ClientRegistry {
+            roster: Mutex::new(roster),
+            canonical: Mutex::new(None),
+            stall_after: chrono::Duration::from_std(stall_after).unwrap_or(chrono::Duration::MAX),
+        }
+    }
+
+    #[inline(always)]
+    fn update(&self, name: &str, identity: Option<Identity>, change: impl FnOnce(&mut Client)) {
+        let mut roster = self.roster.lock().unwrap();
+        let client = roster
+            .clients
+            .entry(name.to_owned())
+            .or_insert_with(|| Client::new(identity.unwrap_or(Identity::Address)));
+        change(client);
+        if !roster.dirty.contains(name) {
+            roster.dirty.insert(name.to_owned());
+        }
+    }
+
+    // every time it authenticates, the way it did can change with the secrets
+    pub fn identified(&self, name: &str, identity: Identity, clv: Option<&str>) {
+        self.update(name, Some(identity), |client| {
+            client.identity = identity;
+            if clv.is_some() {
+                client.clv = clv.map(str::to_owned);
+            }
+        });
+    }
+
+    #[inline(always)]
+    pub fn answered(&self, name: &str, failed: bool) {
+        self.update(name, None, |client| {
+            client.last_seen = Utc::now();
+            client.requests += 1;
+            client.errors += failed as u64;
+        });
+    }
+
+    // lag is how many blocks its head is behind the controlling CL's, None when that can't be told, which leaves it
+    // as it was unless its head stopped moving
+    pub fn forkchoice(&self, name: &str, forkchoice: &ForkchoiceStateV1, lag: Option<u64>) -> SyncState {
+        let mut sync = SyncState::default();
+        self.update(name, None, |client| {
+            let now = Utc::now();
+            if client.forkchoice.as_ref().map(|v| v.headBlockHash) != Some(forkchoice.headBlockHash) {
+                client.head_since = now;
+            }
+            client.forkchoice = Some(forkchoice.clone());
+            let stuck = now - client.head_since >= self.stall_after;
+            sync = match lag {
+                Some(v) if v <= SYNCED_LAG => SyncState::Synced,
+                Some(_) if stuck => SyncState::Stalled,
+                Some(_) => SyncState::Syncing,
+                None if stuck && client.sync != SyncState::Synced => SyncState::Stalled,
+                None => client.sync,
+            };
+            if sync != client.sync {
+                match sync {
+                    SyncState::Stalled => tracing::warn!(
+                        "Follower {} is behind the controlling CL and has had the same head for {}s",
+                        name,
+                        (now - client.head_since).num_seconds()
+                    ),
+                    _ => tracing::info!("Follower {} is {:?}", name, sync),
+                }
+                client.sync = sync;
+            }
+        });
+        sync
+    }
+
+    // the controlling CL's calls, the way answered counts a follower's
+    pub fn canonical_answered(&self, failed: bool) {
+        let mut canonical = self.canonical.lock().unwrap();
+        let client = canonical.get_or_insert_with(|| Client::new(Identity::Jwt));
+        client.last_seen = Utc::now();
+        client.requests += 1;
+        client.errors += failed as u64;
+    }
+
+    // its head is what the followers' sync is told by, so there's no sync of its own
+    pub fn canonical_forkchoice(&self, forkchoice: &ForkchoiceStateV1) {
+        let mut canonical = self.canonical.lock().unwrap();
+        let client = canonical.get_or_insert_with(|| Client::new(Identity::Jwt));
+        if client.forkchoice.as_ref().map(|v| v.headBlockHash) != Some(forkchoice.headBlockHash) {
+            client.head_since = Utc::now();
+        }
+        client.forkchoice = Some(forkchoice.clone());
+        client.sync = SyncState::Synced;
+    }
+
+    // whether it's been answered, by name
+    #[inline(always)]
+    pub fn knows(&self, name: &str) -> bool {
+        self.roster.lock().unwrap().clients.contains_key(name)
+    }
+
+    #[inline(always)]
+    pub fn get(&self, name: &str) -> Option<Client> {
+        self.roster.lock().unwrap().clients.get(name).cloned()
+    }
+
+    #[inline(always)]
+    pub fn canonical(&self) -> Option<Client> {
+        self.canonical.lock().unwrap().clone()
+    }
+
+    // by name
+    pub fn all(&self) -> Vec<(String, Client)> {
+        let roster = self.roster.lock().unwrap();
+        let mut clients: Vec<_> = roster.clients.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        clients.sort_by(|a, b| a.0.cmp(&b.0));
+        clients
+    }
+
+    // writes the followers that changed since the last time, the ones that fail are tried again next time
+    pub async fn persist(&self, db: &dyn Storage) {
+        let changed: Vec<(String, Client)> = {
+            let mut roster = self.roster.lock().unwrap();
+            let dirty: Vec<String> = roster.dirty.drain().collect();
+            dirty.into_iter().filter_map(|name| roster.clients.get(&name).cloned().map(|v| (name, v))).collect()
+        };
+        for (name, client) in changed {
+            let saved = match serde_json::to_string(&client) {
+                Ok(v) => db.put_client(&name, &v).await.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = saved {
+                tracing::error!("Unable to save follower {} to db: {}", name, e);
+                self.roster.lock().unwrap().dirty.insert(name);
+            }
+        }
+    }
+
+    pub fn spawn_persister(self: &Arc<Self>, db: Arc<dyn Storage>, every: Duration) {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            loop {
+                interval.tick().await;
+                registry.persist(&*db).await;
+            }
+        });
+    }
+}
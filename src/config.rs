@@ -0,0 +1,510 @@
+// the --config file, a TOML file with the settings a deployment keeps from one start to the next. it's read before
+// the arguments are, and what it sets is passed on as the arguments it stands for, so anything given on the
+// command line or in the environment still wins
+use crate::{access::IpFilter, methods::MethodFilter, policy::MethodPolicy, ratelimit::RateLimiter, types::State};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsString,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// how often the config file is checked for changes, a SIGHUP checks it right away
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DbBackend {
+    Postgres,
+    Redis,
+    Memory,
+    Sled,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Network {
+    Mainnet,
+    Holesky,
+    Sepolia,
+    Gnosis,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SslMode {
+    Disable,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LogLevelName {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LogRotate {
+    Never,
+    Hourly,
+    Daily,
+}
+
+// the variants are spelled the way the arguments take them
+#[inline(always)]
+fn variant<T: std::fmt::Debug>(v: T) -> String {
+    let name = format!("{:?}", v);
+    let mut spelled = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            spelled.push('-');
+        }
+        spelled.push(c.to_ascii_lowercase());
+    }
+    spelled
+}
+
+// a url the EL or redis can be reached at, checked here so the error names the key
+fn url<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let v = String::deserialize(deserializer)?;
+    reqwest::Url::parse(&v).map_err(|e| serde::de::Error::custom(format!("{}: {}", v, e)))?;
+    Ok(Some(v))
+}
+
+fn urls<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let v = Vec::<String>::deserialize(deserializer)?;
+    for url in &v {
+        reqwest::Url::parse(url).map_err(|e| serde::de::Error::custom(format!("{}: {}", url, e)))?;
+    }
+    Ok(v)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct NodeConfig {
+    #[serde(default, deserialize_with = "url")]
+    url: Option<String>, // --node
+    #[serde(default, deserialize_with = "url")]
+    unauth: Option<String>, // --unauth-node
+    #[serde(default, deserialize_with = "urls")]
+    backups: Vec<String>, // --backup-nodes
+    #[serde(default, deserialize_with = "urls")]
+    verify: Vec<String>, // --verify-nodes
+    timeout: Option<u64>,
+    retries: Option<u32>,
+    breaker_threshold: Option<u32>,
+    breaker_cooldown: Option<u64>,
+    health_interval: Option<u64>,
+}
+
+// one [nodes."url"] table, --node-settings for that node
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct NodeOverridesConfig {
+    timeout: Option<u64>,
+    retries: Option<u32>,
+    jwt_secret: Option<String>,
+    tls_ca: Option<String>,
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+}
+
+impl NodeOverridesConfig {
+    // URL;KEY=VALUE;... the way --node-settings takes it
+    fn arg(self, url: &str) -> String {
+        let keys = [
+            ("timeout", self.timeout.map(|v| v.to_string())),
+            ("retries", self.retries.map(|v| v.to_string())),
+            ("jwt-secret", self.jwt_secret),
+            ("tls-ca", self.tls_ca),
+            ("tls-cert", self.tls_cert),
+            ("tls-key", self.tls_key),
+        ];
+        keys.into_iter().filter_map(|(k, v)| v.map(|v| format!(";{}={}", k, v))).fold(url.to_owned(), |arg, v| arg + &v)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct DbConfig {
+    backend: Option<DbBackend>,
+    #[serde(default, deserialize_with = "url")]
+    url: Option<String>, // --db-url
+    host: Option<String>,
+    port: Option<u16>,
+    user: Option<String>,
+    pass: Option<String>,
+    name: Option<String>,
+    sslmode: Option<SslMode>,
+    ssl_root_cert: Option<String>,
+    ssl_cert: Option<String>,
+    ssl_key: Option<String>,
+    pool_size: Option<u64>,
+    #[serde(default, deserialize_with = "url")]
+    redis_url: Option<String>,
+    sled_path: Option<String>,
+    lru_cache_size: Option<u64>,
+    lru_bodies_size: Option<u64>,
+}
+
+// the controlling CL's own listener
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct CanonicalConfig {
+    port: Option<u16>,
+    listen_addr: Option<String>,
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct LogConfig {
+    level: Option<LogLevelName>,
+    format: Option<LogFormat>,
+    file: Option<String>,
+    max_size: Option<u64>,
+    rotate: Option<LogRotate>,
+    keep: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Config {
+    port: Option<u16>,
+    listen_addr: Option<String>,
+    jwt_secret: Option<String>,
+    el_jwt_secret: Option<String>,
+    client_jwt_secrets: Option<String>,
+    metrics_addr: Option<String>,
+    admin_addr: Option<String>,
+    admin_mode: Option<String>,
+    grpc_addr: Option<String>,
+    admin_token: Option<String>,
+    admin_jwt_secret: Option<String>,
+    network: Option<Network>,
+    chain_id: Option<u64>,
+    seconds_per_slot: Option<u64>,
+    shanghai_time: Option<u64>,
+    cancun_time: Option<u64>,
+    prague_time: Option<u64>,
+    #[serde(default)]
+    client_allow: Vec<String>,
+    #[serde(default)]
+    client_deny: Vec<String>,
+    #[serde(default)]
+    client_rate_limit: Vec<String>,
+    #[serde(default)]
+    client_methods: Vec<String>,
+    #[serde(default)]
+    method_policy: Vec<String>,
+    #[serde(default)]
+    block_builders: Vec<String>,
+    fee_recipient: Option<String>,
+    #[serde(default)]
+    fee_recipient_exceptions: Vec<String>,
+    #[serde(default)]
+    canonical: CanonicalConfig,
+    #[serde(default)]
+    node: NodeConfig,
+    #[serde(default)]
+    nodes: BTreeMap<String, NodeOverridesConfig>, // by url
+    #[serde(default)]
+    db: DbConfig,
+    #[serde(default)]
+    log: LogConfig,
+}
+
+impl Config {
+    // the argument each setting stands for, with its values
+    fn args(self) -> Vec<(&'static str, Vec<String>)> {
+        let one = |name: &'static str, v: Option<String>| v.map(|v| (name, vec![v]));
+        let canonical = self.canonical;
+        let node = self.node;
+        let db = self.db;
+        let log = self.log;
+        let mut args: Vec<_> = [
+            one("port", self.port.map(|v| v.to_string())),
+            one("listen-addr", self.listen_addr),
+            one("jwt-secret", self.jwt_secret),
+            one("el-jwt-secret", self.el_jwt_secret),
+            one("client-jwt-secrets", self.client_jwt_secrets),
+            one("metrics-addr", self.metrics_addr),
+            one("admin-addr", self.admin_addr),
+            one("admin-mode", self.admin_mode),
+            one("grpc-addr", self.grpc_addr),
+            one("admin-token", self.admin_token),
+            one("admin-jwt-secret", self.admin_jwt_secret),
+            one("fee-recipient", self.fee_recipient),
+            one("network", self.network.map(variant)),
+            one("chain-id", self.chain_id.map(|v| v.to_string())),
+            one("seconds-per-slot", self.seconds_per_slot.map(|v| v.to_string())),
+            one("shanghai-time", self.shanghai_time.map(|v| v.to_string())),
+            one("cancun-time", self.cancun_time.map(|v| v.to_string())),
+            one("prague-time", self.prague_time.map(|v| v.to_string())),
+            one("canonical-port", canonical.port.map(|v| v.to_string())),
+            one("canonical-listen-addr", canonical.listen_addr),
+            one("canonical-tls-cert", canonical.tls_cert),
+            one("canonical-tls-key", canonical.tls_key),
+            one("node", node.url),
+            one("unauth-node", node.unauth),
+            one("node-timeout", node.timeout.map(|v| v.to_string())),
+            one("node-retries", node.retries.map(|v| v.to_string())),
+            one("node-breaker-threshold", node.breaker_threshold.map(|v| v.to_string())),
+            one("node-breaker-cooldown", node.breaker_cooldown.map(|v| v.to_string())),
+            one("node-health-interval", node.health_interval.map(|v| v.to_string())),
+            one("db-backend", db.backend.map(variant)),
+            one("db-url", db.url),
+            one("db-host", db.host),
+            one("db-port", db.port.map(|v| v.to_string())),
+            one("db-user", db.user),
+            one("db-pass", db.pass),
+            one("db-name", db.name),
+            one("db-sslmode", db.sslmode.map(variant)),
+            one("db-ssl-root-cert", db.ssl_root_cert),
+            one("db-ssl-cert", db.ssl_cert),
+            one("db-ssl-key", db.ssl_key),
+            one("db-pool-size", db.pool_size.map(|v| v.to_string())),
+            one("redis-url", db.redis_url),
+            one("sled-path", db.sled_path),
+            one("lru-cache-size", db.lru_cache_size.map(|v| v.to_string())),
+            one("lru-bodies-size", db.lru_bodies_size.map(|v| v.to_string())),
+            one("log-level", log.level.map(variant)),
+            one("log-format", log.format.map(variant)),
+            one("log-file", log.file),
+            one("log-max-size", log.max_size.map(|v| v.to_string())),
+            one("log-rotate", log.rotate.map(variant)),
+            one("log-keep", log.keep.map(|v| v.to_string())),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let lists = [
+            ("client-allow", self.client_allow),
+            ("client-deny", self.client_deny),
+            ("client-rate-limit", self.client_rate_limit),
+            ("client-methods", self.client_methods),
+            ("method-policy", self.method_policy),
+            ("block-builders", self.block_builders),
+            ("fee-recipient-exceptions", self.fee_recipient_exceptions),
+            ("backup-nodes", node.backups),
+            ("verify-nodes", node.verify),
+            ("node-settings", self.nodes.into_iter().map(|(url, v)| v.arg(&url)).collect()),
+        ];
+        args.extend(lists.into_iter().filter(|(_, v)| !v.is_empty()));
+        args
+    }
+}
+
+// the environment variable that sets an argument, OE_ and its name in capitals, like OE_DB_URL for --db-url
+#[inline(always)]
+pub fn env_name(name: &str) -> String {
+    format!("OE_{}", name.to_uppercase().replace('-', "_"))
+}
+
+// whether the command line gives name itself, as --name value or --name=value, or the environment does
+#[inline(always)]
+fn given(argv: &[OsString], name: &str) -> bool {
+    let flag = format!("--{}", name);
+    argv.iter().filter_map(|v| v.to_str()).any(|v| v == flag || v.starts_with(&format!("{}=", flag)))
+        || std::env::var_os(env_name(name)).is_some()
+}
+
+// argv with the flags set in the environment, clap only reads it for the arguments that take a value. a flag is
+// set by its variable having any value at all
+pub fn env_flags(argv: Vec<OsString>, flags: &[&str]) -> Vec<OsString> {
+    let set: Vec<_> = flags
+        .iter()
+        .filter(|v| std::env::var_os(env_name(v)).is_some() && !argv.iter().any(|arg| arg.to_str() == Some(&format!("--{}", v))))
+        .map(|v| OsString::from(format!("--{}", v)))
+        .collect();
+    let mut argv = argv.into_iter();
+    argv.next().into_iter().chain(set).chain(argv).collect()
+}
+
+// the --config path, or OE_CONFIG, looked for before clap parses anything so the file can set the required arguments
+pub fn path(argv: &[OsString]) -> Option<String> {
+    let mut args = argv.iter().filter_map(|v| v.to_str());
+    while let Some(v) = args.next() {
+        if v == "--config" {
+            return args.next().map(str::to_owned);
+        }
+        if let Some(v) = v.strip_prefix("--config=") {
+            return Some(v.to_owned());
+        }
+    }
+    std::env::var(env_name("config")).ok()
+}
+
+// the arguments the config file stands for
+fn load(path: &str) -> Result<Vec<(&'static str, Vec<String>)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Unable to read config file {}: {}", path, e))?;
+    let config: Config = toml::from_str(&text).map_err(|e| format!("Invalid config file {}: {}", path, e))?;
+    Ok(config.args())
+}
+
+// argv with what the config file sets that the command line and the environment don't, right after the program name
+pub fn apply(argv: Vec<OsString>, path: &str) -> Result<Vec<OsString>, String> {
+    let mut extra = Vec::new();
+    for (name, values) in load(path)? {
+        if given(&argv, name) {
+            continue;
+        }
+        for v in values {
+            extra.push(OsString::from(format!("--{}", name)));
+            extra.push(OsString::from(v));
+        }
+    }
+    let mut argv = argv.into_iter();
+    Ok(argv.next().into_iter().chain(extra).chain(argv).collect())
+}
+
+// the --log-level the logs are filtered by, read on every event so a reload can change it
+pub struct LogLevel(AtomicU8);
+
+const LEVELS: [tracing::Level; 5] = [
+    tracing::Level::ERROR,
+    tracing::Level::WARN,
+    tracing::Level::INFO,
+    tracing::Level::DEBUG,
+    tracing::Level::TRACE,
+];
+
+impl LogLevel {
+    // anything that isn't a level is info
+    pub fn new(name: &str) -> LogLevel {
+        let level = LogLevel(AtomicU8::new(0));
+        level.set(name);
+        level
+    }
+
+    #[inline(always)]
+    pub fn get(&self) -> tracing::Level {
+        LEVELS[self.0.load(Ordering::Relaxed) as usize]
+    }
+
+    // whether it changed
+    fn set(&self, name: &str) -> bool {
+        let index = match name {
+            "error" => 0,
+            "warn" => 1,
+            "debug" => 3,
+            "trace" => 4,
+            _ => 2,
+        };
+        self.0.swap(index, Ordering::Relaxed) != index
+    }
+}
+
+// what a reload can change without a restart, the rest of the file is only read at startup
+const RELOADABLE: [&str; 8] = [
+    "log-level",
+    "client-allow",
+    "client-deny",
+    "client-rate-limit",
+    "client-methods",
+    "method-policy",
+    "block-builders",
+    "backup-nodes",
+];
+
+// the settings of the reloadable arguments the command line and the environment leave to the file, the ones it
+// doesn't have back to their defaults. nothing changes unless all of them are fine
+fn reload(path: &str, argv: &[OsString], state: &State, log_level: &LogLevel, started: &mut HashMap<&'static str, Vec<String>>) -> Result<(), String> {
+    let args: HashMap<_, _> = load(path)?.into_iter().collect();
+    let setting = |name: &str| match given(argv, name) {
+        true => None,
+        false => Some(args.get(name).map(|v| v.iter().map(String::as_str).collect::<Vec<_>>()).unwrap_or_default()),
+    };
+
+    // the networks go together, either one given elsewhere keeps both
+    let ip_filter = match (setting("client-allow"), setting("client-deny")) {
+        (Some(allow), Some(deny)) => Some(IpFilter::new(allow, deny).map_err(|e| format!("Unable to parse the follower networks: {}", e))?),
+        _ => None,
+    };
+    let rate_limiter = setting("client-rate-limit").map(|v| RateLimiter::new(v).map_err(|e| format!("Unable to parse client-rate-limit: {}", e))).transpose()?;
+    let methods = setting("client-methods").map(|v| MethodFilter::new(v).map_err(|e| format!("Unable to parse client-methods: {}", e))).transpose()?;
+    let policy = setting("method-policy").map(|v| MethodPolicy::new(v).map_err(|e| format!("Unable to parse method-policy: {}", e))).transpose()?;
+    if let Some(v) = setting("backup-nodes") {
+        state.auth_nodes.set_backups(&v.iter().map(|v| v.to_string()).collect::<Vec<_>>())?;
+    }
+
+    if let Some(v) = setting("log-level") {
+        if log_level.set(v.first().copied().unwrap_or("info")) {
+            tracing::callsite::rebuild_interest_cache();
+            tracing::info!("Logging at {} now", log_level.get());
+        }
+    }
+    if let Some(v) = ip_filter {
+        *state.client_ip_filter.write().unwrap() = v;
+    }
+    if let Some(v) = rate_limiter {
+        *state.client_rate_limiter.write().unwrap() = v;
+    }
+    if let Some(v) = methods {
+        *state.client_methods.write().unwrap() = v;
+    }
+    if let Some(v) = policy {
+        *state.method_policy.write().unwrap() = v;
+    }
+    if let Some(v) = setting("block-builders") {
+        *state.block_builders.write().unwrap() = v.into_iter().map(str::to_owned).collect::<HashSet<_>>();
+    }
+
+    let changed: HashSet<_> = args
+        .keys()
+        .chain(started.keys())
+        .filter(|v| !RELOADABLE.contains(v) && !given(argv, v) && args.get(*v) != started.get(*v))
+        .collect();
+    for name in changed {
+        tracing::warn!("--{} changed in {}, it takes a restart", name, path);
+    }
+    *started = args;
+    Ok(())
+}
+
+// rereads the config file when it changes or on a SIGHUP, for the settings that can change without dropping
+// connections or what the controlling CL sent. a file that doesn't load keeps the settings from before it
+pub fn spawn_reloader(path: String, argv: Vec<OsString>, state: Arc<State>, log_level: Arc<LogLevel>) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+        #[cfg(not(unix))]
+        let mut hangup = None;
+
+        let mut interval = tokio::time::interval(RELOAD_POLL_INTERVAL);
+        let mut modified = crate::auth::modified(&path);
+        let mut started: HashMap<_, _> = load(&path).unwrap_or_default().into_iter().collect();
+        loop {
+            let forced = crate::auth::wait_for_check(&mut interval, &mut hangup).await;
+            let now_modified = crate::auth::modified(&path);
+            if !forced && now_modified == modified {
+                continue;
+            }
+            modified = now_modified;
+            match reload(&path, &argv, &state, &log_level, &mut started) {
+                Ok(()) => tracing::info!("Reloaded {}", path),
+                Err(e) => tracing::error!("Unable to reload {}, keeping the settings from before: {}", path, e),
+            }
+        }
+    });
+}
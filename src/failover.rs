@@ -0,0 +1,174 @@
+// which CL controls the EL. the one on /canonical does, unless it stops sending fcUs and one of the
+// --standby-controllers, followers that keep sending theirs, takes over until it's been back for a while.
+// an admin can hand control to any CL, a follower that isn't a standby only has it for as long as it's pinned
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// what the CL on /canonical goes by among the controllers
+pub const CANONICAL: &str = "canonical";
+
+#[derive(Debug)]
+struct Heard {
+    last: Instant,  // its last fcU
+    since: Instant, // the first fcU since it last went quiet
+}
+
+#[derive(Debug)]
+struct FailoverState {
+    order: Vec<String>,    // CANONICAL first, then the standbys by priority, then the followers an admin pinned
+    acting: usize,         // index into order
+    pinned: Option<usize>, // set by an admin, overrides everything else
+    heard: HashMap<usize, Heard>,
+    switches: u64, // how many times control changed hands
+}
+
+#[derive(Debug)]
+pub struct Failover {
+    candidates: usize,  // how many of order take over on their own, CANONICAL and the standbys
+    after: Duration,    // how long the acting one can go without an fcU before the next takes over
+    failback: Duration, // how long one higher up has to keep sending fcUs to take back over
+    state: Mutex<FailoverState>,
+}
+
+impl Failover {
+    pub fn new(standbys: Vec<String>, after: Duration, failback: Duration) -> Failover {
+        let mut order = vec![CANONICAL.to_owned()];
+        order.extend(standbys);
+        // the canonical CL gets its failover time after a start to send its first fcU
+        let now = Instant::now();
+        let heard = HashMap::from([(0, Heard { last: now, since: now })]);
+        Failover {
+            candidates: order.len(),
+            after,
+            failback,
+            state: Mutex::new(FailoverState {
+                order,
+                acting: 0,
+                pinned: None,
+                heard,
+                switches: 0,
+            }),
+        }
+    }
+
+    // with --standby-controllers, or once an admin handed control to a follower
+    #[inline(always)]
+    pub fn is_enabled(&self) -> bool {
+        self.candidates > 1 || self.state.lock().unwrap().order.len() > 1
+    }
+
+    // a controller, or a follower that could be one, sent an fcU
+    pub fn heard(&self, name: &str) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let index = match state.order.iter().position(|v| v == name) {
+            Some(v) => v,
+            None => return,
+        };
+        let heard = state.heard.entry(index).or_insert(Heard { last: now, since: now });
+        if now - heard.last >= self.after {
+            heard.since = now;
+        }
+        heard.last = now;
+    }
+
+    // moves control to whoever should have it now, logging when that changes
+    fn update(&self, state: &mut FailoverState) -> usize {
+        let now = Instant::now();
+        let alive = |i: &usize| state.heard.get(i).is_some_and(|v| now - v.last < self.after);
+        let next = match state.pinned {
+            Some(v) => v,
+            // a follower that was pinned hands control back as soon as it isn't
+            None if state.acting >= self.candidates => (0..self.candidates).find(alive).unwrap_or(0),
+            // the acting one went quiet, the first one that isn't takes over, nobody does if they all did
+            None if !alive(&state.acting) => (0..self.candidates).find(alive).unwrap_or(state.acting),
+            // one higher up that's been back for the failback time takes back over
+            None => (0..state.acting)
+                .find(|i| alive(i) && state.heard.get(i).is_some_and(|v| now - v.since >= self.failback))
+                .unwrap_or(state.acting),
+        };
+        if next != state.acting {
+            let (from, to) = (&state.order[state.acting], &state.order[next]);
+            match (state.pinned, next < state.acting) {
+                (Some(_), _) => tracing::warn!("{} controls the EL instead of {}, as it was set to", to, from),
+                (None, _) if state.acting >= self.candidates => tracing::warn!("{} controls the EL again, {} isn't set to anymore", to, from),
+                (None, true) => tracing::warn!("{} controls the EL again, it's sent fcUs for {:?}", to, self.failback),
+                (None, false) => tracing::warn!("{} controls the EL, {} hasn't sent an fcU for {:?}", to, from, self.after),
+            }
+            state.acting = next;
+            state.switches += 1;
+        }
+        next
+    }
+
+    // changes every time control changes hands, so an fcU sent to the EL before can tell it's no longer the
+    // controlling CL's once it's answered
+    #[inline(always)]
+    pub fn switches(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        self.update(&mut state);
+        state.switches
+    }
+
+    // whether name's engine_ calls go to the EL as the controlling CL's
+    pub fn controls(&self, name: &str) -> bool {
+        if !self.is_enabled() {
+            return name == CANONICAL;
+        }
+        let mut state = self.state.lock().unwrap();
+        let acting = self.update(&mut state);
+        state.order[acting] == name
+    }
+
+    // an admin setting who controls the EL whatever the fcUs say, None goes back to following them. a name that
+    // isn't a controller is a follower, known is whether OE has answered one by that name. returns whether
+    // control changed hands
+    pub fn pin(&self, name: Option<&str>, known: impl Fn(&str) -> bool) -> Result<bool, String> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.acting;
+        state.pinned = match name {
+            Some(v) => match state.order.iter().position(|c| c == v) {
+                Some(i) => Some(i),
+                None if known(v) => {
+                    state.order.push(v.to_owned());
+                    Some(state.order.len() - 1)
+                }
+                None => return Err(format!("{} isn't one of the controllers, or a CL OE has answered", v)),
+            },
+            None => None,
+        };
+        Ok(self.update(&mut state) != before)
+    }
+
+    // who controls the EL, and whether an admin set it to
+    pub fn acting(&self) -> (String, bool) {
+        let mut state = self.state.lock().unwrap();
+        let acting = self.update(&mut state);
+        (state.order[acting].clone(), state.pinned.is_some())
+    }
+
+    // every controller with how long ago its last fcU was, and who's acting
+    pub fn status(&self) -> serde_json::Value {
+        let mut state = self.state.lock().unwrap();
+        let acting = self.update(&mut state);
+        let controllers: Vec<_> = state
+            .order
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                serde_json::json!({
+                    "name": name,
+                    "last_fcu_secs": state.heard.get(&i).map(|v| v.last.elapsed().as_secs()),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "acting": state.order[acting],
+            "pinned": state.pinned.is_some(),
+            "controllers": controllers,
+        })
+    }
+}
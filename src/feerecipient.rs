@@ -0,0 +1,64 @@
+// --fee-recipient, the suggestedFeeRecipient every payloadAttributes that reaches the EL gets, whatever the CL that
+// sent it was set up with. --fee-recipient-exceptions gives a client its own, or leaves it the one it sends
+use std::collections::HashMap;
+
+pub struct FeeRecipients {
+    default: Option<String>,
+    clients: HashMap<String, Option<String>>, // None keeps the client's own
+}
+
+// 0x and 20 bytes of hex, lowercased the way the EL answers with it
+#[inline(always)]
+fn parse_address(input: &str) -> Result<String, String> {
+    let hex = input.strip_prefix("0x").unwrap_or(input);
+    match hex.len() == 40 && hex.chars().all(|v| v.is_ascii_hexdigit()) {
+        true => Ok(format!("0x{}", hex.to_ascii_lowercase())),
+        false => Err(format!("{} is not a 20 byte address", input)),
+    }
+}
+
+impl FeeRecipients {
+    // the exceptions are NAME to keep the client's own, or NAME=ADDRESS to give it that one
+    pub fn new<'a>(default: Option<&str>, exceptions: impl IntoIterator<Item = &'a str>) -> Result<FeeRecipients, String> {
+        let mut clients = HashMap::new();
+        for v in exceptions {
+            let (name, address) = match v.split_once('=') {
+                Some((name, address)) => (name, Some(parse_address(address)?)),
+                None => (v, None),
+            };
+            clients.insert(name.trim().to_owned(), address);
+        }
+        Ok(FeeRecipients {
+            default: default.map(parse_address).transpose()?,
+            clients,
+        })
+    }
+
+    #[inline(always)]
+    fn for_client(&self, client: &str) -> Option<&str> {
+        match self.clients.get(client) {
+            Some(v) => v.as_deref(),
+            None => self.default.as_deref(),
+        }
+    }
+
+    // the fcU with the client's payloadAttributes given its fee recipient, None when there's nothing to change
+    pub fn rewrite(&self, client: &str, body: &str) -> Option<(String, String)> {
+        let address = self.for_client(client)?;
+        // most requests aren't fcUs with attributes, they're not worth parsing
+        if !body.contains("suggestedFeeRecipient") {
+            return None;
+        }
+        let mut request: serde_json::Value = serde_json::from_str(body).ok()?;
+        if !request["method"].as_str()?.starts_with("engine_forkchoiceUpdated") {
+            return None;
+        }
+        let recipient = request["params"].get_mut(1)?.get_mut("suggestedFeeRecipient")?;
+        let previous = recipient.as_str()?.to_owned();
+        if previous.eq_ignore_ascii_case(address) {
+            return None;
+        }
+        *recipient = serde_json::Value::String(address.to_owned());
+        Some((request.to_string(), previous))
+    }
+}
@@ -0,0 +1,118 @@
+// POST /admin/cache/flush, which of OE's caches to empty: every one of them, the ones the methods are answered
+// from, and with from or to only what's kept about those blocks. for a cache gone bad, or an EL swapped for another
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cache {
+    Fcu,
+    NewPayload,
+    ExchangeConfig,
+    PayloadBodies,
+    Block, // the recent payloads eth_getBlockBy* are answered from
+    Blobs,
+    Capabilities,
+    ChainId,
+    ClientVersion,
+    Web3ClientVersion,
+}
+
+impl Cache {
+    pub const ALL: [Cache; 10] = [
+        Cache::Fcu,
+        Cache::NewPayload,
+        Cache::ExchangeConfig,
+        Cache::PayloadBodies,
+        Cache::Block,
+        Cache::Blobs,
+        Cache::Capabilities,
+        Cache::ChainId,
+        Cache::ClientVersion,
+        Cache::Web3ClientVersion,
+    ];
+
+    // what its hit and miss metrics call it, when it has them
+    #[inline(always)]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cache::Fcu => "fcu",
+            Cache::NewPayload => "newpayload",
+            Cache::ExchangeConfig => "exchangeconfig",
+            Cache::PayloadBodies => "payloadbodies",
+            Cache::Block => "block",
+            Cache::Blobs => "blobs",
+            Cache::Capabilities => "capabilities",
+            Cache::ChainId => "chainid",
+            Cache::ClientVersion => "clientversion",
+            Cache::Web3ClientVersion => "web3clientversion",
+        }
+    }
+
+    // the methods it answers, every version of the engine_ ones
+    #[inline(always)]
+    fn methods(&self) -> &'static [&'static str] {
+        match self {
+            Cache::Fcu => &["engine_forkchoiceUpdated"],
+            Cache::NewPayload => &["engine_newPayload"],
+            Cache::ExchangeConfig => &["engine_exchangeTransitionConfiguration"],
+            Cache::PayloadBodies => &["engine_getPayloadBodiesBy"],
+            Cache::Block => &["eth_getBlockByHash", "eth_getBlockByNumber"],
+            Cache::Blobs => &["engine_getBlobs"],
+            Cache::Capabilities => &["engine_exchangeCapabilities"],
+            Cache::ChainId => &["eth_chainId", "net_version"],
+            Cache::ClientVersion => &["engine_getClientVersion"],
+            Cache::Web3ClientVersion => &["web3_clientVersion"],
+        }
+    }
+
+    // whether what it keeps can be told apart by block, and so flushed for a range of them
+    #[inline(always)]
+    pub fn by_block(&self) -> bool {
+        matches!(self, Cache::Fcu | Cache::NewPayload | Cache::PayloadBodies | Cache::Block)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlushRequest {
+    #[serde(default)]
+    pub methods: Vec<String>,
+    pub from: Option<u64>, // block numbers, either one alone is every block from or up to it
+    pub to: Option<u64>,
+}
+
+impl FlushRequest {
+    // the blocks to flush, None for all of them
+    pub fn range(&self) -> Result<Option<(u64, u64)>, String> {
+        if self.from.is_none() && self.to.is_none() {
+            return Ok(None);
+        }
+        let (from, to) = (self.from.unwrap_or(0), self.to.unwrap_or(u64::MAX));
+        match from <= to {
+            true => Ok(Some((from, to))),
+            false => Err(format!("from {} is past to {}", from, to)),
+        }
+    }
+
+    // the caches the methods are answered from, every one without methods. a range leaves out the ones that aren't
+    // kept by block, and naming a method whose cache isn't along with one is an error
+    pub fn caches(&self) -> Result<Vec<Cache>, String> {
+        let by_block = self.range()?.is_some();
+        if self.methods.is_empty() {
+            return Ok(Cache::ALL.into_iter().filter(|v| v.by_block() || !by_block).collect());
+        }
+        let mut caches = Vec::new();
+        for method in &self.methods {
+            let cache = Cache::ALL
+                .into_iter()
+                .find(|v| v.methods().iter().any(|m| method.starts_with(m)))
+                .ok_or_else(|| format!("{} isn't answered from a cache", method))?;
+            if by_block && !cache.by_block() {
+                return Err(format!("{} isn't cached by block, it can only be flushed without from and to", method));
+            }
+            if !caches.contains(&cache) {
+                caches.push(cache);
+            }
+        }
+        Ok(caches)
+    }
+}
@@ -0,0 +1,142 @@
+// the admin API over gRPC on --grpc-addr, for fleet tooling that wants typed calls rather than json. every call
+// answers with what the admin API's endpoint would, read into the messages of proto/admin.proto
+use crate::{auth::AuthError, flush::FlushRequest, types::State};
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+pub mod pb {
+    tonic::include_proto!("openexecution.admin.v1");
+}
+
+pub use pb::admin_server::AdminServer;
+
+pub struct AdminService {
+    state: Arc<State>,
+}
+
+impl AdminService {
+    pub fn new(state: Arc<State>) -> AdminService {
+        AdminService { state }
+    }
+
+    // the same --admin-token or --admin-jwt-secret as the admin API, in the authorization metadata
+    async fn authorize<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        let admin_auth = match &self.state.admin_auth {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        let result = match token {
+            Some(token) => admin_auth.read().await.check(token, self.state.jwt_max_iat_skew),
+            None => Err(AuthError::Missing),
+        };
+        result.map_err(|e| {
+            tracing::warn!("Rejected an admin gRPC call: {}", e);
+            self.state.metrics.auth_failure("grpc", e.reason());
+            Status::unauthenticated(e.to_string())
+        })
+    }
+}
+
+#[inline(always)]
+#[allow(clippy::result_large_err)] // Status is what the calls answer with
+fn reply<T: serde::de::DeserializeOwned>(mut json: serde_json::Value) -> Result<Response<T>, Status> {
+    without_nulls(&mut json);
+    serde_json::from_value(json)
+        .map(Response::new)
+        .map_err(|e| Status::internal(format!("Unable to make the reply: {}", e)))
+}
+
+// the admin API's json has a null for what isn't known yet, like the capabilities before the EL has answered
+// exchangeCapabilities. left out it's the field's default, unset for an optional one and empty for a repeated one
+fn without_nulls(json: &mut serde_json::Value) {
+    match json {
+        serde_json::Value::Object(v) => {
+            v.retain(|_, v| !v.is_null());
+            v.values_mut().for_each(without_nulls);
+        }
+        serde_json::Value::Array(v) => v.iter_mut().for_each(without_nulls),
+        _ => {}
+    }
+}
+
+// the last fcU is kept as the engine API's request and response, the message only has what's in them
+#[inline(always)]
+fn last_fcu(fcu: &mut serde_json::Value) {
+    let fcu = match fcu.as_object_mut() {
+        Some(v) => v,
+        None => return,
+    };
+    let (request, response) = (fcu.remove("request").unwrap_or_default(), fcu.remove("response").unwrap_or_default());
+    let state = &request["params"][0];
+    let status = &response["result"]["payloadStatus"];
+    fcu.insert("method".to_owned(), request["method"].clone());
+    fcu.insert(
+        "forkchoice_state".to_owned(),
+        serde_json::json!({
+            "head_block_hash": state["headBlockHash"],
+            "safe_block_hash": state["safeBlockHash"],
+            "finalized_block_hash": state["finalizedBlockHash"],
+        }),
+    );
+    fcu.insert("status".to_owned(), status["status"].clone());
+    fcu.insert("latest_valid_hash".to_owned(), status["latestValidHash"].clone());
+}
+
+#[tonic::async_trait]
+impl pb::admin_server::Admin for AdminService {
+    async fn list_clients(&self, request: Request<pb::ListClientsRequest>) -> Result<Response<pb::ListClientsReply>, Status> {
+        self.authorize(&request).await?;
+        reply(crate::admin_clients(&self.state))
+    }
+
+    async fn get_head(&self, request: Request<pb::GetHeadRequest>) -> Result<Response<pb::Head>, Status> {
+        self.authorize(&request).await?;
+        let mut head = crate::admin_head(&self.state).await;
+        last_fcu(&mut head["last_fcu"]);
+        reply(head)
+    }
+
+    async fn get_stats(&self, request: Request<pb::GetStatsRequest>) -> Result<Response<pb::Stats>, Status> {
+        self.authorize(&request).await?;
+        reply(self.state.metrics.stats())
+    }
+
+    async fn get_controller(&self, request: Request<pb::GetControllerRequest>) -> Result<Response<pb::Controllers>, Status> {
+        self.authorize(&request).await?;
+        reply(self.state.failover.status())
+    }
+
+    async fn set_controller(&self, request: Request<pb::SetControllerRequest>) -> Result<Response<pb::Controllers>, Status> {
+        self.authorize(&request).await?;
+        let controller = request.into_inner().controller;
+        match crate::set_controller(&self.state, controller.as_deref()).await {
+            Ok(v) => reply(v),
+            Err(e) => Err(Status::invalid_argument(e)),
+        }
+    }
+
+    async fn flush_cache(&self, request: Request<pb::FlushCacheRequest>) -> Result<Response<pb::FlushCacheReply>, Status> {
+        self.authorize(&request).await?;
+        let request = request.into_inner();
+        let request = FlushRequest {
+            methods: request.methods,
+            from: request.from,
+            to: request.to,
+        };
+        match crate::flush_caches(&self.state, &request).await {
+            Ok(v) => reply(v),
+            Err((status, e)) => {
+                let message = e["error"].as_str().unwrap_or_default().to_owned();
+                match status == axum::http::StatusCode::BAD_REQUEST {
+                    true => Err(Status::invalid_argument(message)),
+                    false => Err(Status::internal(message)),
+                }
+            }
+        }
+    }
+}
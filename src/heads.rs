@@ -0,0 +1,153 @@
+// the blocks the controlling CL sent lately, to tell how many blocks behind a follower's head is, and which
+// blocks a reorg left behind along with what was cached about them
+use crate::primitives::H256;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::Mutex,
+};
+
+// a bit over 3 hours of blocks, a follower further behind than that isn't following
+const RECENT_BLOCKS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheKind {
+    Fcu,        // an fcU with the block as its head
+    NewPayload, // the block's newPayload
+}
+
+impl fmt::Display for CacheKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheKind::Fcu => write!(f, "fcU"),
+            CacheKind::NewPayload => write!(f, "newPayload"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Block {
+    number: Option<u64>, // None until the controlling CL sends its newPayload
+    parent: Option<H256>,
+    cached: Vec<(CacheKind, String)>, // db keys of the answers cached about it
+}
+
+#[derive(Default)]
+pub struct RecentBlocks {
+    blocks: Mutex<(HashMap<H256, Block>, VecDeque<H256>)>, // by hash, and the hashes oldest first
+}
+
+#[inline(always)]
+fn entry(blocks: &mut (HashMap<H256, Block>, VecDeque<H256>), hash: H256) -> &mut Block {
+    let (by_hash, order) = blocks;
+    if !by_hash.contains_key(&hash) {
+        order.push_back(hash);
+        if order.len() > RECENT_BLOCKS {
+            if let Some(v) = order.pop_front() {
+                by_hash.remove(&v);
+            }
+        }
+    }
+    by_hash.entry(hash).or_default()
+}
+
+impl RecentBlocks {
+    pub fn insert(&self, hash: H256, number: u64, parent: Option<H256>) {
+        let mut blocks = self.blocks.lock().unwrap();
+        let block = entry(&mut blocks, hash);
+        block.number = Some(number);
+        block.parent = parent;
+    }
+
+    #[inline(always)]
+    pub fn number(&self, hash: &H256) -> Option<u64> {
+        self.blocks.lock().unwrap().0.get(hash).and_then(|v| v.number)
+    }
+
+    // an answer about the block was written to the db under key
+    pub fn cached(&self, hash: H256, kind: CacheKind, key: &str) {
+        let mut blocks = self.blocks.lock().unwrap();
+        let block = entry(&mut blocks, hash);
+        if !block.cached.iter().any(|(k, v)| *k == kind && v == key) {
+            block.cached.push((kind, key.to_owned()));
+        }
+    }
+
+    // the blocks on old's branch that aren't on new's, newest first. empty when new descends from old, or when
+    // there aren't enough of their ancestors here to tell. the ones found before running out are orphaned all the same
+    pub fn orphaned(&self, old: &H256, new: &H256) -> Vec<H256> {
+        let blocks = self.blocks.lock().unwrap();
+        let by_hash = &blocks.0;
+        let parent = |v: &H256| by_hash.get(v).and_then(|v| v.parent);
+        let (mut new_number, mut old_number) = match (by_hash.get(new).and_then(|v| v.number), by_hash.get(old).and_then(|v| v.number)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Vec::new(),
+        };
+        let (mut new, mut old) = (*new, *old);
+        let mut orphaned = Vec::new();
+        while new_number > old_number {
+            new = match parent(&new) {
+                Some(v) => v,
+                None => return Vec::new(),
+            };
+            new_number -= 1;
+        }
+        while old_number > new_number {
+            orphaned.push(old);
+            old = match parent(&old) {
+                Some(v) => v,
+                None => return orphaned,
+            };
+            old_number -= 1;
+        }
+        while new != old {
+            orphaned.push(old);
+            match (parent(&new), parent(&old)) {
+                (Some(a), Some(b)) => (new, old) = (a, b),
+                _ => return orphaned,
+            }
+        }
+        orphaned
+    }
+
+    // the keys of what was cached about the blocks, which are kept so a reorg back to them can still be told
+    pub fn take_cached(&self, hashes: &[H256]) -> Vec<(CacheKind, String)> {
+        let mut blocks = self.blocks.lock().unwrap();
+        let mut keys = Vec::new();
+        for hash in hashes {
+            if let Some(v) = blocks.0.get_mut(hash) {
+                keys.append(&mut v.cached);
+            }
+        }
+        keys
+    }
+
+    // the blocks numbered from to to, of the ones here
+    pub fn numbered(&self, from: u64, to: u64) -> Vec<H256> {
+        let blocks = self.blocks.lock().unwrap();
+        blocks
+            .0
+            .iter()
+            .filter(|(_, v)| v.number.is_some_and(|v| v >= from && v <= to))
+            .map(|(hash, _)| *hash)
+            .collect()
+    }
+
+    // like take_cached, only the keys of one kind
+    pub fn take_cached_kind(&self, hashes: &[H256], kind: CacheKind) -> Vec<String> {
+        let mut blocks = self.blocks.lock().unwrap();
+        let mut keys = Vec::new();
+        for hash in hashes {
+            if let Some(v) = blocks.0.get_mut(hash) {
+                v.cached.retain(|(k, key)| match *k == kind {
+                    true => {
+                        keys.push(key.clone());
+                        false
+                    }
+                    false => true,
+                });
+            }
+        }
+        keys
+    }
+}
@@ -0,0 +1,67 @@
+// work that followers sending the same request would each repeat, like waiting on the controlling CL's fcU for a
+// head or asking the EL to build the same block. the first one does it and the rest get its answer. nothing is
+// kept once the answer is in, that's what the cache is for
+use std::{collections::HashMap, future::Future, sync::Mutex};
+use tokio::sync::watch;
+
+pub struct InFlight<T: Clone> {
+    calls: Mutex<HashMap<String, watch::Receiver<Option<T>>>>,
+}
+
+// takes the call out once the one doing it is done, or went away without an answer
+struct Done<'a, T: Clone> {
+    calls: &'a Mutex<HashMap<String, watch::Receiver<Option<T>>>>,
+    key: &'a str,
+}
+
+impl<T: Clone> Drop for Done<'_, T> {
+    fn drop(&mut self) {
+        self.calls.lock().unwrap().remove(self.key);
+    }
+}
+
+impl<T: Clone> InFlight<T> {
+    pub fn new() -> InFlight<T> {
+        InFlight {
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // the answer for key, and whether it was another request's. when the one doing the work goes away without an
+    // answer, like a follower that hung up, one of the waiting ones does it instead
+    pub async fn run<F, Fut>(&self, key: &str, work: F) -> (T, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        loop {
+            // someone's answer to wait for, or the sender to give everyone ours
+            let waiting = {
+                let mut calls = self.calls.lock().unwrap();
+                match calls.get(key) {
+                    Some(v) => Ok(v.clone()),
+                    None => {
+                        let (tx, rx) = watch::channel(None);
+                        calls.insert(key.to_owned(), rx);
+                        Err(tx)
+                    }
+                }
+            };
+            match waiting {
+                Ok(mut rx) => {
+                    if rx.changed().await.is_ok() {
+                        if let Some(v) = rx.borrow().clone() {
+                            return (v, true);
+                        }
+                    }
+                }
+                Err(tx) => {
+                    let _done = Done { calls: &self.calls, key };
+                    let answer = work().await;
+                    let _ = tx.send(Some(answer.clone()));
+                    return (answer, false);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,37 @@
+// a unix socket for followers on the same host, who the file's permissions let in instead of a jwt
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use tokio::net::{UnixListener, UnixStream};
+
+// what the followers on the socket are logged and rate limited as, they have no address or name to go by
+pub const IPC_CLIENT: &str = "ipc";
+
+// a socket left behind by an openexecution that didn't shut down cleanly is replaced, any other file is not touched
+pub fn bind(path: &str, mode: u32) -> Result<UnixListener, String> {
+    match std::fs::symlink_metadata(path) {
+        Ok(v) if v.file_type().is_socket() => {
+            std::fs::remove_file(path).map_err(|e| format!("Unable to remove the old {}: {}", path, e))?
+        }
+        Ok(_) => return Err(format!("{} exists and isn't a socket", path)),
+        Err(_) => {}
+    }
+
+    let listener = UnixListener::bind(path).map_err(|e| format!("Unable to listen on {}: {}", path, e))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("Unable to set the permissions of {}: {}", path, e))?;
+    Ok(listener)
+}
+
+// an accept that fails is logged and skipped, hyper would stop serving the socket otherwise
+pub fn incoming(listener: UnixListener) -> impl hyper::server::accept::Accept<Conn = UnixStream, Error = std::io::Error> {
+    hyper::server::accept::from_stream(futures::stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => return Some((Ok(stream), listener)),
+                Err(e) => {
+                    tracing::error!("Unable to accept a connection on the unix socket: {}", e);
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                }
+            }
+        }
+    }))
+}
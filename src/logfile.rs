@@ -0,0 +1,99 @@
+// the --log-file, rotated by size or by the hour or day so a node that runs for months doesn't fill its disk
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    period: Option<u64>, // the hour or day since the epoch the file was started in
+    max_bytes: u64,      // 0 never rotates on size
+    every: Option<u64>,  // seconds, on the utc hour or day
+    keep: usize,         // old files kept as path.1 (the newest) to path.keep
+}
+
+#[inline(always)]
+fn period_of(time: SystemTime, every: Option<u64>) -> Option<u64> {
+    every.map(|v| time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / v)
+}
+
+#[inline(always)]
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut v = path.as_os_str().to_owned();
+    v.push(format!(".{}", n));
+    v.into()
+}
+
+impl RotatingFile {
+    pub fn open(path: &str, max_bytes: u64, every: Option<u64>, keep: usize) -> io::Result<RotatingFile> {
+        let path = PathBuf::from(path);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        // a file left from before a restart belongs to the hour or day it was last written in
+        let period = period_of(metadata.modified().unwrap_or_else(|_| SystemTime::now()), every);
+        Ok(RotatingFile {
+            path,
+            file,
+            written: metadata.len(),
+            period,
+            max_bytes,
+            every,
+            keep,
+        })
+    }
+
+    #[inline(always)]
+    fn due(&self, len: usize) -> bool {
+        // a line is never split across files, an empty file takes it however long it is
+        if self.max_bytes > 0 && self.written > 0 && self.written + len as u64 > self.max_bytes {
+            return true;
+        }
+        self.every.is_some() && self.period != period_of(SystemTime::now(), self.every)
+    }
+
+    // path.keep goes, every other old file moves up one and the current one becomes path.1
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let _ = std::fs::remove_file(numbered(&self.path, self.keep));
+            for n in (1..self.keep).rev() {
+                let from = numbered(&self.path, n);
+                if from.exists() {
+                    std::fs::rename(&from, numbered(&self.path, n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, numbered(&self.path, 1))?;
+        }
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.written = 0;
+        self.period = period_of(SystemTime::now(), self.every);
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.due(buf.len()) {
+            // tracing can't be used from inside its own writer, and a log that can't rotate is better than none
+            if let Err(e) = self.rotate() {
+                eprintln!("Unable to rotate {}: {}", self.path.display(), e);
+                // tried again after another max_bytes or in the next hour or day, not on every line
+                self.written = 0;
+                self.period = period_of(SystemTime::now(), self.every);
+            }
+        }
+        let len = self.file.write(buf)?;
+        self.written += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -0,0 +1,49 @@
+// maintenance mode, for restarting or pruning the EL without the CLs noticing: nothing goes to the EL, followers are
+// answered SYNCING or from what OE has, and the controlling CL is too, or has its calls held until it's over
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Mode {
+    pub queue: bool, // hold the controlling CL's calls instead of answering them
+    pub since: DateTime<Utc>,
+}
+
+pub struct Maintenance(watch::Sender<Option<Mode>>); // None when it's off
+
+impl Maintenance {
+    pub fn new() -> Maintenance {
+        Maintenance(watch::channel(None).0)
+    }
+
+    #[inline(always)]
+    pub fn current(&self) -> Option<Mode> {
+        *self.0.borrow()
+    }
+
+    // turning it on again only changes queue, it's been on since the first time
+    pub fn start(&self, queue: bool) -> Mode {
+        self.0.send_modify(|v| {
+            let since = v.map_or_else(Utc::now, |v| v.since);
+            *v = Some(Mode { queue, since });
+        });
+        self.current().unwrap()
+    }
+
+    // whether it was on
+    pub fn stop(&self) -> bool {
+        self.0.send_replace(None).is_some()
+    }
+
+    // returns once calls aren't held anymore, it's off or answers without the EL now, with what it is then
+    pub async fn released(&self) -> Option<Mode> {
+        let mut mode = self.0.subscribe();
+        // the sender is in State, it's never dropped while a call waits on it
+        let released = match mode.wait_for(|v| !v.is_some_and(|v| v.queue)).await {
+            Ok(v) => *v,
+            Err(_) => None,
+        };
+        released
+    }
+}
@@ -0,0 +1,47 @@
+// --client-methods, the non-engine_ calls followers can make, which go to the EL's public RPC. a name ending in *
+// is every method it starts with, like eth_get*
+use std::collections::HashSet;
+
+// what followers could always call, when --client-methods isn't given
+const DEFAULT_PREFIXES: [&str; 3] = ["eth_", "net_", "web3_"];
+
+pub struct MethodFilter {
+    methods: HashSet<String>,
+    prefixes: Vec<String>,
+}
+
+impl MethodFilter {
+    pub fn new<'a>(methods: impl IntoIterator<Item = &'a str>) -> Result<MethodFilter, String> {
+        let mut filter = MethodFilter {
+            methods: HashSet::new(),
+            prefixes: Vec::new(),
+        };
+        for method in methods.into_iter().map(str::trim).filter(|v| !v.is_empty()) {
+            if method.starts_with("engine_") {
+                return Err(format!("{} is an engine_ method, followers get those as they are", method));
+            }
+            match method.strip_suffix('*') {
+                Some("") => return Err("* on its own would let followers call anything the EL has".to_owned()),
+                Some(prefix) => filter.prefixes.push(prefix.to_owned()),
+                None => {
+                    filter.methods.insert(method.to_owned());
+                }
+            }
+        }
+        if filter.methods.is_empty() && filter.prefixes.is_empty() {
+            filter.prefixes = DEFAULT_PREFIXES.iter().map(|v| v.to_string()).collect();
+        }
+        Ok(filter)
+    }
+
+    #[inline(always)]
+    pub fn allows(&self, method: &str) -> bool {
+        self.methods.contains(method) || self.prefixes.iter().any(|v| method.starts_with(v.as_str()))
+    }
+
+    // whether it's what followers could always call, without a --client-methods
+    #[inline(always)]
+    pub fn is_default(&self) -> bool {
+        self.methods.is_empty() && self.prefixes.iter().map(String::as_str).eq(DEFAULT_PREFIXES)
+    }
+}
@@ -0,0 +1,333 @@
+// what openexecution counts for prometheus, served as text on --metrics-addr
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder};
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+// from a cached answer to a getPayloadBodies, with the spec's 8s and 10s timeouts as their own buckets
+const LATENCY_BUCKETS: [f64; 13] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 8.0, 10.0];
+// openexecution's own share is mostly well under a millisecond, unless the db is slow or an fcU waits for the canonical one
+const INTERNAL_BUCKETS: [f64; 14] = [0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+// most reorgs are a block or two, anything past an epoch is worth seeing on its own
+const REORG_BUCKETS: [f64; 9] = [1.0, 2.0, 3.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0];
+
+tokio::task_local! {
+    // how long the EL took over every call made for the request being answered
+    static UPSTREAM_TIME: Cell<Duration>;
+}
+
+// runs the answering of a request, and says how much of its time was spent waiting on the EL
+pub async fn timed<F: Future>(answer: F) -> (F::Output, Duration) {
+    UPSTREAM_TIME
+        .scope(Cell::new(Duration::ZERO), async {
+            let output = answer.await;
+            (output, UPSTREAM_TIME.with(|v| v.get()))
+        })
+        .await
+}
+
+pub struct Metrics {
+    registry: Registry,
+    started: Instant,
+    requests: IntCounterVec,          // endpoint, method, status
+    request_seconds: HistogramVec,    // endpoint, method
+    internal_seconds: HistogramVec,   // endpoint, method
+    upstream_seconds: HistogramVec,   // node, method
+    upstream_errors: IntCounterVec,   // node, method
+    upstream_retries: IntCounterVec,  // node, method
+    breaker_open: IntGaugeVec,        // node
+    maintenance: IntGaugeVec,
+    cache: IntCounterVec,             // kind, result
+    cache_writes: IntCounterVec,      // kind
+    cache_write_bytes: IntCounterVec, // kind
+    auth_failures: IntCounterVec,     // endpoint, reason
+    banned: IntCounterVec,            // follower
+    follower_requests: IntCounterVec, // follower
+    follower_errors: IntCounterVec,   // follower
+    follower_last_seen: IntGaugeVec,  // follower
+    follower_head_lag: IntGaugeVec,   // follower
+    reorgs: IntCounterVec,
+    reorg_depth: HistogramVec,
+    verifications: IntCounterVec, // node, kind, outcome
+    coalesced: IntCounterVec,     // kind
+    fee_recipient_rewrites: IntCounterVec, // client
+}
+
+#[inline(always)]
+fn counter(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    let counter = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+    registry.register(Box::new(counter.clone())).unwrap();
+    counter
+}
+
+#[inline(always)]
+fn gauge(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> IntGaugeVec {
+    let gauge = IntGaugeVec::new(Opts::new(name, help), labels).unwrap();
+    registry.register(Box::new(gauge.clone())).unwrap();
+    gauge
+}
+
+#[inline(always)]
+fn histogram(registry: &Registry, name: &str, help: &str, buckets: &[f64], labels: &[&str]) -> HistogramVec {
+    let histogram = HistogramVec::new(HistogramOpts::new(name, help).buckets(buckets.to_vec()), labels).unwrap();
+    registry.register(Box::new(histogram.clone())).unwrap();
+    histogram
+}
+
+#[derive(Clone, Copy)]
+struct CacheStats {
+    hits: u64,
+    stale: u64,
+    misses: u64,
+    writes: u64,
+    bytes: u64,
+}
+
+impl CacheStats {
+    #[inline(always)]
+    fn since(&self, earlier: &CacheStats) -> CacheStats {
+        CacheStats {
+            hits: self.hits - earlier.hits,
+            stale: self.stale - earlier.stale,
+            misses: self.misses - earlier.misses,
+            writes: self.writes - earlier.writes,
+            bytes: self.bytes - earlier.bytes,
+        }
+    }
+
+    #[inline(always)]
+    fn is_empty(&self) -> bool {
+        self.hits + self.stale + self.misses + self.writes == 0
+    }
+}
+
+impl std::fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lookups = self.hits + self.stale + self.misses;
+        write!(f, "{} hits, {} stale, {} misses", self.hits, self.stale, self.misses)?;
+        // stale answers still kept a call from the EL
+        if let Some(v) = ((self.hits + self.stale) * 100).checked_div(lookups) {
+            write!(f, " ({}% without the EL)", v)?;
+        }
+        write!(f, ", {} written ({} KiB)", self.writes, self.bytes / 1024)
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let registry = Registry::new_custom(Some("oe".to_owned()), None).unwrap();
+        Metrics {
+            requests: counter(&registry, "requests_total", "JSON-RPC requests answered, by HTTP status", &["endpoint", "method", "status"]),
+            request_seconds: histogram(&registry, "request_duration_seconds", "How long answering a JSON-RPC request took", &LATENCY_BUCKETS, &["endpoint", "method"]),
+            internal_seconds: histogram(
+                &registry,
+                "internal_duration_seconds",
+                "How long answering a JSON-RPC request took, less the time spent waiting on the EL",
+                &INTERNAL_BUCKETS,
+                &["endpoint", "method"],
+            ),
+            upstream_seconds: histogram(&registry, "upstream_duration_seconds", "How long an EL node took to answer, retries included", &LATENCY_BUCKETS, &["node", "method"]),
+            upstream_errors: counter(&registry, "upstream_errors_total", "Calls an EL node didn't answer, after any retries", &["node", "method"]),
+            upstream_retries: counter(&registry, "upstream_retries_total", "Calls sent to an EL node again after a connection error", &["node", "method"]),
+            breaker_open: gauge(&registry, "breaker_open", "1 while followers are answered without the EL node", &["node"]),
+            maintenance: gauge(&registry, "maintenance", "Whether an admin put openexecution in maintenance mode, so nothing goes to the EL", &[]),
+            cache: counter(&registry, "cache_total", "Requests openexecution could answer from what it has, or not", &["kind", "result"]),
+            cache_writes: counter(&registry, "cache_writes_total", "Responses written to the db", &["kind"]),
+            cache_write_bytes: counter(&registry, "cache_write_bytes_total", "Bytes of responses written to the db", &["kind"]),
+            auth_failures: counter(&registry, "auth_failures_total", "Requests rejected for their JWT", &["endpoint", "reason"]),
+            banned: counter(&registry, "banned_requests_total", "Follower requests rejected because an admin banned the follower or its address", &["follower"]),
+            follower_requests: counter(&registry, "follower_requests_total", "JSON-RPC requests from each follower", &["follower"]),
+            follower_errors: counter(&registry, "follower_errors_total", "JSON-RPC requests from each follower answered with an error", &["follower"]),
+            follower_last_seen: gauge(&registry, "follower_last_seen_timestamp_seconds", "When each follower last sent a request", &["follower"]),
+            follower_head_lag: gauge(&registry, "follower_head_lag_blocks", "How many blocks the head of each follower's last fcU is behind the controlling CL's", &["follower"]),
+            reorgs: counter(&registry, "reorgs_total", "Times the controlling CL's new head wasn't a descendant of its last one", &[]),
+            reorg_depth: histogram(&registry, "reorg_depth_blocks", "How many of the controlling CL's blocks each reorg left behind", &REORG_BUCKETS, &[]),
+            verifications: counter(&registry, "verifications_total", "How the answers of the --verify-nodes compared to the EL's", &["node", "kind", "outcome"]),
+            coalesced: counter(&registry, "coalesced_total", "Follower requests answered with what an identical one in flight got", &["kind"]),
+            fee_recipient_rewrites: counter(&registry, "fee_recipient_rewrites_total", "payloadAttributes whose suggestedFeeRecipient --fee-recipient replaced", &["client"]),
+            registry,
+            started: Instant::now(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn request(&self, endpoint: &str, method: &str, status: axum::http::StatusCode, started: Instant, upstream: Duration) {
+        let elapsed = started.elapsed();
+        self.requests.with_label_values(&[endpoint, method, status.as_str()]).inc();
+        self.request_seconds.with_label_values(&[endpoint, method]).observe(elapsed.as_secs_f64());
+        self.internal_seconds.with_label_values(&[endpoint, method]).observe(elapsed.saturating_sub(upstream).as_secs_f64());
+    }
+
+    #[inline(always)]
+    pub fn upstream(&self, node: &str, method: &str, answered: bool, started: Instant) {
+        let elapsed = started.elapsed();
+        // calls made outside of answering a request, like the readiness probe's, aren't in a timed one
+        let _ = UPSTREAM_TIME.try_with(|v| v.set(v.get() + elapsed));
+        self.upstream_seconds.with_label_values(&[node, method]).observe(elapsed.as_secs_f64());
+        if !answered {
+            self.upstream_errors.with_label_values(&[node, method]).inc();
+        }
+    }
+
+    #[inline(always)]
+    pub fn upstream_retry(&self, node: &str, method: &str) {
+        self.upstream_retries.with_label_values(&[node, method]).inc();
+    }
+
+    #[inline(always)]
+    pub fn breaker(&self, node: &str, open: bool) {
+        self.breaker_open.with_label_values(&[node]).set(open as i64);
+    }
+
+    #[inline(always)]
+    pub fn maintenance(&self, on: bool) {
+        self.maintenance.with_label_values(&[]).set(on as i64);
+    }
+
+    #[inline(always)]
+    pub fn cache(&self, kind: &str, hit: bool) {
+        self.cache.with_label_values(&[kind, if hit { "hit" } else { "miss" }]).inc();
+    }
+
+    // a cached fcU or newPayload answer that isn't VALID is stale, the EL may well know better by now
+    #[inline(always)]
+    pub fn cache_hit(&self, kind: &str, valid: bool) {
+        self.cache.with_label_values(&[kind, if valid { "hit" } else { "stale" }]).inc();
+    }
+
+    #[inline(always)]
+    pub fn cache_write(&self, kind: &str, bytes: usize) {
+        self.cache_writes.with_label_values(&[kind]).inc();
+        self.cache_write_bytes.with_label_values(&[kind]).inc_by(bytes as u64);
+    }
+
+    #[inline(always)]
+    fn cache_stats(&self, kind: &str) -> CacheStats {
+        CacheStats {
+            hits: self.cache.with_label_values(&[kind, "hit"]).get(),
+            stale: self.cache.with_label_values(&[kind, "stale"]).get(),
+            misses: self.cache.with_label_values(&[kind, "miss"]).get(),
+            writes: self.cache_writes.with_label_values(&[kind]).get(),
+            bytes: self.cache_write_bytes.with_label_values(&[kind]).get(),
+        }
+    }
+
+    // what the fcU and newPayload caches did since the last summary, in the log every so often
+    pub fn spawn_cache_summary(self: &Arc<Self>, every: Duration) {
+        let metrics = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            interval.tick().await;
+            let mut last = [metrics.cache_stats("fcu"), metrics.cache_stats("newpayload")];
+            loop {
+                interval.tick().await;
+                let now = [metrics.cache_stats("fcu"), metrics.cache_stats("newpayload")];
+                let [fcu, newpayload] = [now[0].since(&last[0]), now[1].since(&last[1])];
+                last = now;
+                if fcu.is_empty() && newpayload.is_empty() {
+                    continue;
+                }
+                tracing::info!("Cache over the last {}s: fcU {}; newPayload {}", every.as_secs(), fcu, newpayload);
+            }
+        });
+    }
+
+    #[inline(always)]
+    pub fn auth_failure(&self, endpoint: &str, reason: &str) {
+        self.auth_failures.with_label_values(&[endpoint, reason]).inc();
+    }
+
+    #[inline(always)]
+    pub fn banned(&self, follower: &str) {
+        self.banned.with_label_values(&[follower]).inc();
+    }
+
+    #[inline(always)]
+    pub fn follower(&self, follower: &str, failed: bool) {
+        self.follower_requests.with_label_values(&[follower]).inc();
+        if failed {
+            self.follower_errors.with_label_values(&[follower]).inc();
+        }
+        self.follower_last_seen.with_label_values(&[follower]).set(chrono::Utc::now().timestamp());
+    }
+
+    #[inline(always)]
+    pub fn follower_head_lag(&self, follower: &str, blocks: u64) {
+        self.follower_head_lag.with_label_values(&[follower]).set(blocks as i64);
+    }
+
+    #[inline(always)]
+    pub fn reorg(&self, depth: usize) {
+        self.reorgs.with_label_values(&[]).inc();
+        self.reorg_depth.with_label_values(&[]).observe(depth as f64);
+    }
+
+    #[inline(always)]
+    pub fn verification(&self, node: &str, kind: &str, outcome: &str) {
+        self.verifications.with_label_values(&[node, kind, outcome]).inc();
+    }
+
+    #[inline(always)]
+    pub fn coalesced(&self, kind: &str) {
+        self.coalesced.with_label_values(&[kind]).inc();
+    }
+
+    #[inline(always)]
+    pub fn fee_recipient_rewrite(&self, client: &str) {
+        self.fee_recipient_rewrites.with_label_values(&[client]).inc();
+    }
+
+    // the counters since openexecution started as json, for GET /admin/stats. each one by the label that tells the
+    // most, added up over the others
+    pub fn stats(&self) -> serde_json::Value {
+        let families = self.registry.gather();
+        let by = |name: &str, label: &str| -> BTreeMap<String, u64> {
+            let mut totals = BTreeMap::new();
+            for family in families.iter().filter(|v| v.get_name() == format!("oe_{}", name)) {
+                for metric in family.get_metric() {
+                    let key = metric.get_label().iter().find(|v| v.get_name() == label).map_or("", |v| v.get_value());
+                    *totals.entry(key.to_owned()).or_default() += metric.get_counter().get_value() as u64;
+                }
+            }
+            totals
+        };
+        let mut cache: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+        for family in families.iter().filter(|v| v.get_name() == "oe_cache_total") {
+            for metric in family.get_metric() {
+                let label = |name: &str| metric.get_label().iter().find(|v| v.get_name() == name).map_or("", |v| v.get_value()).to_owned();
+                cache.entry(label("kind")).or_default().insert(label("result"), metric.get_counter().get_value() as u64);
+            }
+        }
+        let total = |name: &str| by(name, "").values().sum::<u64>();
+        serde_json::json!({
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "requests": {
+                "total": total("requests_total"),
+                "by_endpoint": by("requests_total", "endpoint"),
+                "by_method": by("requests_total", "method"),
+                "by_follower": by("follower_requests_total", "follower"),
+            },
+            "follower_errors": by("follower_errors_total", "follower"),
+            "cache": cache,
+            "upstream_errors": by("upstream_errors_total", "node"),
+            "upstream_retries": by("upstream_retries_total", "node"),
+            "reorgs": total("reorgs_total"),
+            "auth_failures": total("auth_failures_total"),
+            "banned_requests": total("banned_requests_total"),
+            "coalesced": total("coalesced_total"),
+        })
+    }
+
+    // the prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!("Unable to encode the metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
@@ -0,0 +1,53 @@
+// the networks --network knows, so their fork timestamps don't have to be copied by hand. anything given as its own
+// argument wins over the preset
+pub struct Network {
+    pub name: &'static str,
+    pub chain_id: u64,
+    pub seconds_per_slot: u64,
+    pub shanghai: u64,
+    pub cancun: u64,
+    pub prague: u64,
+}
+
+pub const NETWORKS: [Network; 4] = [
+    Network {
+        name: "mainnet",
+        chain_id: 1,
+        seconds_per_slot: 12,
+        shanghai: 1681338455,
+        cancun: 1710338135,
+        prague: 1746612311,
+    },
+    Network {
+        name: "holesky",
+        chain_id: 17000,
+        seconds_per_slot: 12,
+        shanghai: 1696000704,
+        cancun: 1707305664,
+        prague: 1740434112,
+    },
+    Network {
+        name: "sepolia",
+        chain_id: 11155111,
+        seconds_per_slot: 12,
+        shanghai: 1677557088,
+        cancun: 1706655072,
+        prague: 1741159776,
+    },
+    Network {
+        name: "gnosis",
+        chain_id: 100,
+        seconds_per_slot: 5,
+        shanghai: 1690889660,
+        cancun: 1710181820,
+        prague: 1746021820,
+    },
+];
+
+// what a slot is without a --network or --seconds-per-slot, mainnet's
+pub const DEFAULT_SECONDS_PER_SLOT: u64 = 12;
+
+#[inline(always)]
+pub fn named(name: &str) -> Option<&'static Network> {
+    NETWORKS.iter().find(|v| v.name == name)
+}
@@ -0,0 +1,198 @@
+// the EL nodes engine_ calls can go to, --node and then the --backup-nodes. they all go to the first one whose
+// circuit breaker is closed, which the health checks keep up to date between calls
+use crate::types::{Node, NodeClientOptions, UpstreamPolicy};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+// what one node gets instead of the settings every node shares, from --node-settings. an EL in the same
+// datacenter and a backup across the country don't want the same timeouts, and the remote one may want TLS
+#[derive(Debug, Clone, Default)]
+pub struct NodeOverrides {
+    pub timeout: Option<Duration>,  // --node-timeout
+    pub retries: Option<u32>,       // --node-retries
+    pub jwt_secret: Option<String>, // --el-jwt-secret
+    pub tls_ca: Option<String>,     // trusted for its https url on top of the system's CAs
+    pub tls_cert: Option<String>,   // client certificate, for an EL behind a proxy that asks for one
+    pub tls_key: Option<String>,
+}
+
+impl NodeOverrides {
+    // URL;KEY=VALUE;... with timeout, retries, jwt-secret, tls-ca, tls-cert and tls-key as the keys
+    pub fn parse(v: &str) -> Result<(String, NodeOverrides), String> {
+        let mut parts = v.split(';');
+        let url = parts.next().unwrap_or_default();
+        reqwest::Url::parse(url).map_err(|e| format!("{} is not a url: {}", url, e))?;
+        let mut overrides = NodeOverrides::default();
+        for part in parts.filter(|v| !v.is_empty()) {
+            let (key, value) = part.split_once('=').ok_or_else(|| format!("{} is not KEY=VALUE", part))?;
+            match key {
+                "timeout" => match value.parse::<u64>() {
+                    Ok(0) => return Err("timeout has to be at least 1 second".to_owned()),
+                    Ok(v) => overrides.timeout = Some(Duration::from_secs(v)),
+                    Err(e) => return Err(format!("Unable to parse timeout: {}", e)),
+                },
+                "retries" => overrides.retries = Some(value.parse().map_err(|e| format!("Unable to parse retries: {}", e))?),
+                "jwt-secret" => overrides.jwt_secret = Some(value.to_owned()),
+                "tls-ca" => overrides.tls_ca = Some(value.to_owned()),
+                "tls-cert" => overrides.tls_cert = Some(value.to_owned()),
+                "tls-key" => overrides.tls_key = Some(value.to_owned()),
+                _ => return Err(format!("{} is not one of timeout, retries, jwt-secret, tls-ca, tls-cert or tls-key", key)),
+            }
+        }
+        if overrides.tls_cert.is_some() != overrides.tls_key.is_some() {
+            return Err(format!("{} needs both tls-cert and tls-key", url));
+        }
+        Ok((url.to_owned(), overrides))
+    }
+
+    fn client(&self, mut builder: reqwest::ClientBuilder) -> Result<reqwest::Client, String> {
+        let read = |path: &str| std::fs::read(path).map_err(|e| format!("Unable to read {}: {}", path, e));
+        if let Some(path) = &self.tls_ca {
+            let cert = reqwest::Certificate::from_pem(&read(path)?).map_err(|e| format!("Unable to parse {}: {}", path, e))?;
+            builder = builder.add_root_certificate(cert);
+        }
+        if let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) {
+            let identity = reqwest::Identity::from_pkcs8_pem(&read(cert)?, &read(key)?).map_err(|e| format!("Unable to parse {} and {}: {}", cert, key, e))?;
+            builder = builder.identity(identity);
+        }
+        builder.build().map_err(|e| e.to_string())
+    }
+}
+
+// what every EL node is set up with, kept for the backups a config reload adds
+pub struct NodeSettings {
+    pub client: NodeClientOptions,
+    pub upstream: UpstreamPolicy,
+    pub overrides: HashMap<String, NodeOverrides>, // by url, from --node-settings
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+    pub metrics: Arc<crate::metrics::Metrics>,
+}
+
+impl NodeSettings {
+    pub fn node(&self, name: &str, url: &str) -> Result<Arc<Node>, String> {
+        let overrides = self.overrides.get(url).cloned().unwrap_or_default();
+        let jwt = match &overrides.jwt_secret {
+            Some(path) => Some(jsonwebtoken::EncodingKey::from_secret(&crate::auth::read_secret(path)?)),
+            None => None,
+        };
+        Ok(Arc::new(Node {
+            name: name.to_string(),
+            client: overrides.client(self.client.builder())?,
+            url: url.to_string(),
+            upstream: UpstreamPolicy {
+                other: overrides.timeout.unwrap_or(self.upstream.other),
+                retries: overrides.retries.unwrap_or(self.upstream.retries),
+            },
+            jwt,
+            breaker: crate::breaker::CircuitBreaker::new(self.breaker_threshold, self.breaker_cooldown),
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
+
+pub struct NodePool {
+    nodes: RwLock<Vec<Arc<Node>>>, // by priority
+    active: AtomicUsize,           // index into nodes
+    moved: AtomicBool,             // the calls moved without the health checks, the node hasn't been brought to head
+    named: AtomicUsize,            // backups named so far, so one added later doesn't share a metrics label
+    settings: NodeSettings,
+}
+
+impl NodePool {
+    pub fn new(nodes: Vec<Arc<Node>>, settings: NodeSettings) -> NodePool {
+        NodePool {
+            named: AtomicUsize::new(nodes.len() - 1),
+            nodes: RwLock::new(nodes),
+            active: AtomicUsize::new(0),
+            moved: AtomicBool::new(false),
+            settings,
+        }
+    }
+
+    #[inline(always)]
+    pub fn has_backups(&self) -> bool {
+        self.nodes.read().unwrap().len() > 1
+    }
+
+    #[inline(always)]
+    pub fn active(&self) -> Arc<Node> {
+        let nodes = self.nodes.read().unwrap();
+        nodes[self.active.load(Ordering::Relaxed).min(nodes.len() - 1)].clone()
+    }
+
+    #[inline(always)]
+    pub fn all(&self) -> Vec<Arc<Node>> {
+        self.nodes.read().unwrap().clone()
+    }
+
+    // the node at url, or one set up from its --node-settings when it isn't one of them, for calls that go to it
+    // alone. any other url is refused, the calls would be signed with a secret of ours for whoever is there
+    pub fn configured(&self, url: &str) -> Result<Arc<Node>, String> {
+        if let Some(v) = self.nodes.read().unwrap().iter().find(|v| v.url == url) {
+            return Ok(v.clone());
+        }
+        match self.settings.overrides.contains_key(url) {
+            true => self.settings.node("replay", url).map_err(|e| format!("Unable to set up the client for {}: {}", url, e)),
+            false => Err(format!("{} isn't one of --node, --backup-nodes or --node-settings", url)),
+        }
+    }
+
+    // moves the calls to the first node that's up, the node they moved to if they did. when they're all down
+    // the calls stay where they are
+    pub fn update(&self) -> Option<Arc<Node>> {
+        let nodes = self.nodes.read().unwrap();
+        let next = nodes.iter().position(|v| !v.breaker.is_open())?;
+        let previous = self.active.swap(next, Ordering::Relaxed);
+        let moved = self.moved.swap(false, Ordering::Relaxed);
+        if previous == next {
+            return moved.then(|| nodes[next].clone());
+        }
+        let (from, to) = (&nodes[previous], &nodes[next]);
+        match next < previous {
+            true => tracing::warn!("{} is back, engine_ calls go to it again instead of {}", to.url, from.url),
+            false => tracing::warn!("{} is down, engine_ calls go to {} instead", from.url, to.url),
+        }
+        Some(to.clone())
+    }
+
+    // swaps in a new list of backups, the ones that were already there keep their circuit breakers. if the calls
+    // were going to one that's gone they go back to --node, and the next health check brings it to head
+    pub fn set_backups(&self, urls: &[String]) -> Result<(), String> {
+        if self.settings.breaker_threshold == 0 && !urls.is_empty() {
+            return Err("backup-nodes need a node-breaker-threshold above 0 to tell when a node is down".to_owned());
+        }
+        let mut nodes = self.nodes.write().unwrap();
+        if nodes[1..].iter().map(|v| &v.url).eq(urls.iter()) {
+            return Ok(());
+        }
+        let mut next = vec![nodes[0].clone()];
+        for url in urls {
+            match nodes[1..].iter().find(|v| v.url == *url) {
+                Some(v) => next.push(v.clone()),
+                None => {
+                    let name = format!("backup-{}", self.named.fetch_add(1, Ordering::Relaxed) + 1);
+                    next.push(self.settings.node(&name, url).map_err(|e| format!("Unable to set up the client for {}: {}", url, e))?);
+                }
+            }
+        }
+        let active = &nodes[self.active.load(Ordering::Relaxed)];
+        match next.iter().position(|v| Arc::ptr_eq(v, active)) {
+            Some(v) => self.active.store(v, Ordering::Relaxed),
+            None => {
+                tracing::warn!("{} isn't one of the backup nodes anymore, engine_ calls go to {} again", active.url, next[0].url);
+                self.active.store(0, Ordering::Relaxed);
+                self.moved.store(true, Ordering::Relaxed);
+            }
+        }
+        tracing::info!("Backup nodes are now {:?}", urls);
+        *nodes = next;
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+// the payloadIds of the blocks --block-builders start on the EL. each follower gets ids of openexecution's own in
+// place of the EL's, so none of them can fetch a payload another one, or the controlling CL, started
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// a payload is fetched within the slot it was started in, this leaves a few to spare
+const PAYLOAD_ID_TTL: Duration = Duration::from_secs(300);
+
+pub struct PayloadIds {
+    salt: [u8; 32],                                       // so ids can't be worked out from the EL's
+    ids: Mutex<HashMap<String, (String, String, Instant)>>, // ours -> the follower, the EL's, when it was given
+}
+
+impl PayloadIds {
+    pub fn new() -> PayloadIds {
+        PayloadIds {
+            salt: rand::random(),
+            ids: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // the same follower asking for the same payload again gets the same id
+    pub fn issue(&self, follower: &str, el_id: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt);
+        hasher.update(follower.as_bytes());
+        hasher.update([0]);
+        hasher.update(el_id.as_bytes());
+        let ours = format!("0x{}", hex::encode(&hasher.finalize()[..8]));
+
+        let mut ids = self.ids.lock().unwrap();
+        ids.retain(|_, v| v.2.elapsed() < PAYLOAD_ID_TTL);
+        ids.insert(ours.clone(), (follower.to_owned(), el_id.to_owned(), Instant::now()));
+        ours
+    }
+
+    // the EL's id for one the follower was given
+    pub fn resolve(&self, follower: &str, ours: &str) -> Option<String> {
+        let ids = self.ids.lock().unwrap();
+        ids.get(ours).filter(|v| v.0 == follower && v.2.elapsed() < PAYLOAD_ID_TTL).map(|v| v.1.clone())
+    }
+}
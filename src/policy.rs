@@ -0,0 +1,103 @@
+// --method-policy, what becomes of a method for the controlling CL, every follower or one of them: the way OE
+// always handles it, sent to the EL as it is, answered from what OE has, or rejected. a method ending in * is every
+// method it starts with, and the most specific rule for a call is the one it gets
+use std::collections::HashMap;
+
+// who the rules without one are for, and who a follower without rules of its own gets the rules of
+pub const FOLLOWERS: &str = "followers";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Default, // what OE does without a policy
+    Forward, // to the EL, without looking in OE's cache
+    Cache,   // from OE's cache, without the EL
+    Reject,
+}
+
+impl Action {
+    #[inline(always)]
+    fn parse(input: &str) -> Option<Action> {
+        match input {
+            "default" => Some(Action::Default),
+            "forward" => Some(Action::Forward),
+            "cache" => Some(Action::Cache),
+            "reject" => Some(Action::Reject),
+            _ => None,
+        }
+    }
+}
+
+// a follower's fcU would move the EL's head and its getPayload would hand it the controlling CL's block, they're
+// only ever answered by OE however they're forwarded
+#[inline(always)]
+pub fn forwardable(method: &str) -> bool {
+    !method.starts_with("engine_forkchoiceUpdated") && !method.starts_with("engine_getPayloadV")
+}
+
+#[derive(Default)]
+pub struct MethodPolicy {
+    rules: HashMap<String, Vec<(String, bool, Action)>>, // by who, (method or prefix, whether it's a prefix, action)
+}
+
+// a rule is [WHO:]METHOD=ACTION, for the followers when there's no WHO
+#[inline(always)]
+fn parse_rule(input: &str) -> Result<(&str, &str, Action), String> {
+    let (rule, action) = input.trim().rsplit_once('=').ok_or_else(|| format!("{} is not a [WHO:]METHOD=ACTION", input))?;
+    let action = Action::parse(action.trim()).ok_or_else(|| format!("{} in {} is not default, forward, cache or reject", action, input))?;
+    let (who, method) = match rule.rsplit_once(':') {
+        Some((who, method)) => (who.trim(), method.trim()),
+        None => (FOLLOWERS, rule.trim()),
+    };
+    match (who, method) {
+        (_, "") | ("", _) => Err(format!("{} is not a [WHO:]METHOD=ACTION", input)),
+        (crate::failover::CANONICAL, _) if action == Action::Cache => Err("the controlling CL's calls can't be answered from the cache, it's what fills it".to_owned()),
+        _ => Ok((who, method, action)),
+    }
+}
+
+impl MethodPolicy {
+    pub fn new<'a>(rules: impl IntoIterator<Item = &'a str>) -> Result<MethodPolicy, String> {
+        let mut policy = MethodPolicy::default();
+        for rule in rules.into_iter().filter(|v| !v.trim().is_empty()) {
+            let (who, method, action) = parse_rule(rule)?;
+            let (method, prefix) = match method.strip_suffix('*') {
+                Some(v) => (v, true),
+                None => (method, false),
+            };
+            let rules = policy.rules.entry(who.to_owned()).or_default();
+            if rules.iter().any(|(m, p, _)| m == method && *p == prefix) {
+                return Err(format!("{} has more than one rule for {}{}", who, method, if prefix { "*" } else { "" }));
+            }
+            rules.push((method.to_owned(), prefix, action));
+        }
+        Ok(policy)
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // the method's own rule over a prefix's, and the longest prefix over the rest
+    #[inline(always)]
+    fn find(&self, who: &str, method: &str) -> Option<Action> {
+        self.rules
+            .get(who)?
+            .iter()
+            .filter(|(m, prefix, _)| match prefix {
+                true => method.starts_with(m.as_str()),
+                false => method == m,
+            })
+            .max_by_key(|(m, prefix, _)| (!prefix, m.len()))
+            .map(|(_, _, action)| *action)
+    }
+
+    // client is a follower's name or address, or canonical for the controlling CL
+    pub fn action(&self, client: &str, method: &str) -> Action {
+        let found = match client {
+            crate::failover::CANONICAL => self.find(client, method),
+            _ => self.find(client, method).or_else(|| self.find(FOLLOWERS, method)),
+        };
+        found.unwrap_or(Action::Default)
+    }
+}
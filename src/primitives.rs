@@ -0,0 +1,190 @@
+// hex encoded primitives for the engine API types
+// malformed values are rejected when deserializing, and Display always prints the full 0x prefixed hex
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// some clients send quantities with leading zeros or hex without the 0x prefix, permissive mode lets that through
+static PERMISSIVE: AtomicBool = AtomicBool::new(false);
+
+#[inline(always)]
+pub fn set_permissive(permissive: bool) {
+    PERMISSIVE.store(permissive, Ordering::Relaxed);
+}
+
+#[inline(always)]
+fn is_permissive() -> bool {
+    PERMISSIVE.load(Ordering::Relaxed)
+}
+
+#[inline(always)]
+fn strip_hex_prefix(input: &str) -> Result<&str, String> {
+    match input.strip_prefix("0x") {
+        Some(v) => Ok(v),
+        None if is_permissive() => Ok(input),
+        None => Err(format!("{} is missing the 0x prefix", input)),
+    }
+}
+
+macro_rules! impl_fixed_bytes {
+    ($name:ident, $inner:ty, $len:expr) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct $name(pub $inner);
+
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(input: &str) -> Result<Self, Self::Err> {
+                let hex_part = strip_hex_prefix(input)?;
+                if hex_part.len() != $len * 2 {
+                    return Err(format!(
+                        "{} is {} hex chars long, expected {}",
+                        input,
+                        hex_part.len(),
+                        $len * 2
+                    ));
+                }
+                let bytes = hex::decode(hex_part).map_err(|e| format!("{} is not hex: {}", input, e))?;
+                Ok($name(<$inner>::from_slice(&bytes)))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "0x{}", hex::encode(self.0.as_bytes()))
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.to_string())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let input = String::deserialize(deserializer)?;
+                input.parse().map_err(de::Error::custom)
+            }
+        }
+    };
+}
+
+macro_rules! impl_quantity {
+    ($name:ident, $inner:ty, $len:expr) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, PartialOrd, Ord)]
+        pub struct $name(pub $inner);
+
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(input: &str) -> Result<Self, Self::Err> {
+                let hex_part = strip_hex_prefix(input)?;
+                if hex_part.is_empty() {
+                    return Err(format!("{} is not a {} byte quantity", input, $len));
+                }
+                // a QUANTITY is encoded without leading zeros, "0x0" being the only way to write zero
+                if hex_part.len() > 1 && hex_part.starts_with('0') && !is_permissive() {
+                    return Err(format!("{} has leading zeros", input));
+                }
+                let hex_part = match hex_part.trim_start_matches('0') {
+                    "" => "0",
+                    v => v,
+                };
+                if hex_part.len() > $len * 2 {
+                    return Err(format!("{} is not a {} byte quantity", input, $len));
+                }
+                <$inner>::from_str_radix(hex_part, 16)
+                    .map($name)
+                    .map_err(|e| format!("{} is not hex: {:?}", input, e))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "0x{:x}", self.0)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.to_string())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let input = String::deserialize(deserializer)?;
+                input.parse().map_err(de::Error::custom)
+            }
+        }
+    };
+}
+
+impl_fixed_bytes!(H256, ethereum_types::H256, 32);
+impl_fixed_bytes!(Address, ethereum_types::H160, 20);
+impl_fixed_bytes!(Bloom, ethereum_types::Bloom, 256);
+impl_quantity!(U64, ethereum_types::U64, 8);
+impl_quantity!(U256, ethereum_types::U256, 32);
+
+impl U64 {
+    #[inline(always)]
+    pub fn as_u64(&self) -> u64 {
+        self.0.as_u64()
+    }
+}
+
+// variable length DATA, like extraData or a transaction. it's kept as the string it came in as,
+// so forwarding it doesn't re-encode anything, but it has to be valid hex to get in
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Bytes(pub String);
+
+impl Bytes {
+    #[inline(always)]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    #[inline(always)]
+    pub fn to_vec(&self) -> Result<Vec<u8>, String> {
+        let hex_part = strip_hex_prefix(&self.0)?;
+        hex::decode(hex_part).map_err(|e| format!("{} is not hex: {}", self.0, e))
+    }
+}
+
+impl FromStr for Bytes {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let hex_part = strip_hex_prefix(input)?;
+        if hex_part.len() % 2 != 0 {
+            return Err(format!("{} has an odd number of hex chars", input));
+        }
+        if let Some(v) = hex_part.chars().find(|v| !v.is_ascii_hexdigit()) {
+            return Err(format!("{} is not hex: invalid character {:?}", input, v));
+        }
+        Ok(Bytes(input.to_owned()))
+    }
+}
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let input = String::deserialize(deserializer)?;
+        input.parse().map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,92 @@
+// token buckets for the followers, so one that floods openexecution can't take the EL away from the controlling CL
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+
+// past this many buckets the ones that have filled back up are dropped, a full bucket is the same as no bucket
+const MAX_BUCKETS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+pub struct RateLimiter {
+    default: Option<f64>, // requests per second for methods without their own budget, unlimited if None
+    methods: HashMap<String, f64>,
+    buckets: Mutex<HashMap<(String, String), Bucket>>, // (client, method) -> bucket
+}
+
+// a budget is METHOD=RATE, or just RATE for every method without its own, in requests per second
+#[inline(always)]
+fn parse_budget(input: &str) -> Result<(Option<&str>, f64), String> {
+    let (method, rate) = match input.trim().split_once('=') {
+        Some((method, rate)) => (Some(method.trim()), rate.trim()),
+        None => (None, input.trim()),
+    };
+    match rate.parse::<f64>() {
+        Ok(v) if v.is_finite() && v > 0.0 => Ok((method, v)),
+        _ => Err(format!("{} is not a METHOD=RATE or RATE with a rate above 0", input)),
+    }
+}
+
+impl RateLimiter {
+    pub fn new<'a>(budgets: impl IntoIterator<Item = &'a str>) -> Result<RateLimiter, String> {
+        let mut default = None;
+        let mut methods = HashMap::new();
+        for budget in budgets {
+            match parse_budget(budget)? {
+                (Some(method), rate) => {
+                    if methods.insert(method.to_owned(), rate).is_some() {
+                        return Err(format!("{} has more than one rate limit", method));
+                    }
+                }
+                (None, rate) => {
+                    if default.replace(rate).is_some() {
+                        return Err("more than one rate limit for every method".to_owned());
+                    }
+                }
+            }
+        }
+        Ok(RateLimiter {
+            default,
+            methods,
+            buckets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.default.is_none() && self.methods.is_empty()
+    }
+
+    // takes a token from the client's bucket for the method, false if it's empty.
+    // a bucket holds a second's worth of requests, at least one, and refills at the method's rate
+    pub fn check(&self, client: &str, method: &str) -> bool {
+        let rate = match self.methods.get(method).copied().or(self.default) {
+            Some(v) => v,
+            None => return true,
+        };
+        let burst = rate.max(1.0);
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS {
+            let methods = &self.methods;
+            let default = self.default;
+            buckets.retain(|(_, method), bucket| {
+                let rate = methods.get(method).copied().or(default).unwrap_or(f64::INFINITY);
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < rate.max(1.0)
+            });
+        }
+
+        let bucket = buckets
+            .entry((client.to_owned(), method.to_owned()))
+            .or_insert(Bucket { tokens: burst, updated: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
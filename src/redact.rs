@@ -0,0 +1,65 @@
+// JWTs scrubbed out of every log line, whatever logged them, so a log shared to debug something can't be replayed
+// against openexecution or the EL. the Authorization header itself is never logged, the secrets never are either
+use std::{borrow::Cow, io};
+use tracing_subscriber::fmt::MakeWriter;
+
+const REDACTED: &[u8] = b"<redacted jwt>";
+
+#[inline(always)]
+fn is_base64url(v: u8) -> bool {
+    v.is_ascii_alphanumeric() || v == b'-' || v == b'_'
+}
+
+// a JWT's header is json, so base64url of it always starts with eyJ
+pub fn jwts(line: &[u8]) -> Cow<'_, [u8]> {
+    let mut redacted: Option<Vec<u8>> = None;
+    let mut copied = 0; // how much of line is in redacted already
+    let mut i = 0;
+    while i + 3 <= line.len() {
+        if &line[i..i + 3] != b"eyJ" || (i > 0 && is_base64url(line[i - 1])) {
+            i += 1;
+            continue;
+        }
+        let end = i + line[i..].iter().take_while(|v| is_base64url(**v) || **v == b'.').count();
+        let token = &line[i..end];
+        if token.split(|v| *v == b'.').filter(|v| !v.is_empty()).count() == 3 && token.iter().filter(|v| **v == b'.').count() == 2 {
+            let out = redacted.get_or_insert_with(|| Vec::with_capacity(line.len()));
+            out.extend_from_slice(&line[copied..i]);
+            out.extend_from_slice(REDACTED);
+            copied = end;
+        }
+        i = end;
+    }
+    match redacted {
+        Some(mut v) => {
+            v.extend_from_slice(&line[copied..]);
+            Cow::Owned(v)
+        }
+        None => Cow::Borrowed(line),
+    }
+}
+
+// wraps the writers the log layers write to
+pub struct Redacting<M>(pub M);
+
+pub struct RedactingWriter<W>(W);
+
+impl<W: io::Write> io::Write for RedactingWriter<W> {
+    // tracing writes a whole line at once, so a JWT is never split across writes
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_all(&jwts(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+}
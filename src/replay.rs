@@ -0,0 +1,45 @@
+// POST /admin/replay, the newPayloads OE kept sent again to another EL and then an fcU to the last of them, so an EL
+// that replaced the old one, or was started fresh, gets near head from what OE has instead of waiting on its peers
+use crate::{primitives::H256, types::ExecutionStatus};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplayRequest {
+    pub node: String, // the engine API of the EL to replay to, one of --node, --backup-nodes or --node-settings
+    pub from: Option<u64>, // block numbers, every block kept without them
+    pub to: Option<u64>,
+}
+
+impl ReplayRequest {
+    #[inline(always)]
+    pub fn range(&self) -> Result<(u64, u64), String> {
+        let (from, to) = (self.from.unwrap_or(0), self.to.unwrap_or(u64::MAX));
+        match from <= to {
+            true => Ok((from, to)),
+            false => Err(format!("from {} is past to {}", from, to)),
+        }
+    }
+}
+
+// one newPayload sent again, and what the EL made of it
+#[derive(Debug, Serialize)]
+pub struct Replayed {
+    pub number: u64,
+    pub hash: H256,
+    pub status: ExecutionStatus,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReplayReport {
+    pub payloads: Vec<Replayed>,
+    pub fcu: Option<ExecutionStatus>, // the status the EL answered the fcU with, None when it wasn't sent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>, // what stopped the replay
+}
+
+// an EL that found a payload invalid won't take the ones after it either
+#[inline(always)]
+pub fn goes_on(status: &ExecutionStatus) -> bool {
+    !matches!(status, ExecutionStatus::INVALID | ExecutionStatus::INVALID_BLOCK_HASH)
+}
@@ -0,0 +1,94 @@
+// stopping openexecution without cutting off the calls it's in the middle of, so a restart doesn't look like
+// an EL going offline to the CLs
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tokio::sync::{watch, Notify};
+
+pub struct Shutdown {
+    requested: watch::Sender<bool>,
+    sockets: AtomicUsize, // websockets still open, hyper stops tracking a connection once it's upgraded
+    idle: Notify,
+}
+
+// held by a websocket for as long as it's open
+pub struct SocketGuard(Arc<Shutdown>);
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        if self.0.sockets.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Arc<Shutdown> {
+        Arc::new(Shutdown {
+            requested: watch::channel(false).0,
+            sockets: AtomicUsize::new(0),
+            idle: Notify::new(),
+        })
+    }
+
+    // a SIGTERM, or a ctrl-c for someone running it by hand, starts the shutdown
+    pub fn spawn_signal_listener(self: &Arc<Self>) {
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            let terminate = async {
+                match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                    Ok(mut v) => {
+                        v.recv().await;
+                    }
+                    Err(e) => {
+                        tracing::error!("Unable to listen for SIGTERM: {}", e);
+                        std::future::pending::<()>().await;
+                    }
+                }
+            };
+            #[cfg(not(unix))]
+            let terminate = std::future::pending::<()>();
+
+            tokio::select! {
+                _ = terminate => tracing::info!("Got SIGTERM, shutting down"),
+                _ = tokio::signal::ctrl_c() => tracing::info!("Got ctrl-c, shutting down"),
+            }
+            shutdown.requested.send_replace(true);
+        });
+    }
+
+    #[inline(always)]
+    pub fn is_requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    pub async fn requested(&self) {
+        let mut requested = self.requested.subscribe();
+        let _ = requested.wait_for(|v| *v).await;
+    }
+
+    #[inline(always)]
+    pub fn track_socket(self: &Arc<Self>) -> SocketGuard {
+        self.sockets.fetch_add(1, Ordering::SeqCst);
+        SocketGuard(self.clone())
+    }
+
+    // once every websocket has closed
+    pub async fn sockets_closed(&self) {
+        loop {
+            // a Notified gets notify_waiters from the moment it's made, so a close between the check and the await isn't missed
+            let idle = self.idle.notified();
+            if self.sockets.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    #[inline(always)]
+    pub fn open_sockets(&self) -> usize {
+        self.sockets.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,1712 @@
+// where openexecution keeps what it learned from the canonical CL, so followers can be answered from it.
+// handlers only talk to the Storage trait, every backend stores the same strings under the same keys
+use async_trait::async_trait;
+use redis::AsyncCommands;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    error::Error,
+    fmt,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Debug)]
+pub struct StorageError(pub String);
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for StorageError {}
+
+impl From<tokio_postgres::Error> for StorageError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+impl From<deadpool_postgres::PoolError> for StorageError {
+    fn from(e: deadpool_postgres::PoolError) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+impl From<refinery::Error> for StorageError {
+    fn from(e: refinery::Error) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+impl From<sled::Error> for StorageError {
+    fn from(e: sled::Error) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+impl From<redis::RedisError> for StorageError {
+    fn from(e: redis::RedisError) -> Self {
+        StorageError(e.to_string())
+    }
+}
+
+// the cached responses a flush can drop all of
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Table {
+    Fcu,
+    NewPayload,
+    ExchangeConfig,
+    PayloadBodies, // with the block numbers
+}
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    // fcU responses, keyed by ForkchoiceUpdatedRequest::cache_key
+    async fn get_fcu(&self, key: &str) -> Result<Option<String>, StorageError>;
+    async fn put_fcu(&self, key: &str, response: &str) -> Result<(), StorageError>;
+    // for the blocks a reorg left behind
+    async fn remove_fcu(&self, key: &str) -> Result<(), StorageError>;
+
+    // newPayload responses, keyed by NewPayloadRequest::cache_key
+    async fn get_newpayload(&self, key: &str) -> Result<Option<String>, StorageError>;
+    async fn put_newpayload(&self, key: &str, response: &str) -> Result<(), StorageError>;
+    async fn remove_newpayload(&self, key: &str) -> Result<(), StorageError>;
+
+    // the last exchangeTransitionConfiguration response, there's only ever one
+    async fn get_exchangeconfig(&self) -> Result<Option<String>, StorageError>;
+    async fn put_exchangeconfig(&self, response: &str) -> Result<(), StorageError>;
+
+    // the canonical CL's last VALID fcU with its response, saved on shutdown and read back on start
+    async fn get_last_fcu(&self) -> Result<Option<String>, StorageError>;
+    async fn put_last_fcu(&self, pair: &str) -> Result<(), StorageError>;
+
+    // the followers seen so far, by name, each a json record of what's known about it
+    async fn get_clients(&self) -> Result<HashMap<String, String>, StorageError>;
+    async fn put_client(&self, name: &str, record: &str) -> Result<(), StorageError>;
+
+    // the bans an admin set, by follower name or address, each a json record of the ban
+    async fn get_bans(&self) -> Result<HashMap<String, String>, StorageError>;
+    async fn put_ban(&self, client: &str, record: &str) -> Result<(), StorageError>;
+    async fn remove_ban(&self, client: &str) -> Result<(), StorageError>;
+
+    // payload bodies by block hash, only the hashes that are stored are in the map
+    async fn get_payload_bodies(&self, block_hashes: &[String]) -> Result<HashMap<String, String>, StorageError>;
+    async fn put_payload_body(&self, block_hash: &str, body: &str) -> Result<(), StorageError>;
+
+    // which block is at which height, as told by the canonical CL
+    async fn put_payload_number(&self, block_number: i64, block_hash: &str) -> Result<(), StorageError>;
+    // the highest block number stored
+    async fn head(&self) -> Result<Option<i64>, StorageError>;
+    // every known number in [start, end] with its body, None if the block is known but its body isn't
+    async fn get_payload_bodies_by_range(
+        &self,
+        start: i64,
+        end: i64,
+    ) -> Result<HashMap<i64, Option<String>>, StorageError>;
+
+    // drops the numbers and bodies of blocks below keep_from, returns how many blocks were dropped
+    async fn prune(&self, keep_from: i64) -> Result<u64, StorageError>;
+
+    // drops everything in the table, for POST /admin/cache/flush. returns how many entries were dropped
+    async fn clear(&self, table: Table) -> Result<u64, StorageError>;
+    // drops the numbers and bodies of blocks start to end, returns how many blocks were dropped
+    async fn clear_blocks(&self, start: i64, end: i64) -> Result<u64, StorageError>;
+
+    // drops every cached response older than age, returns how many rows were dropped.
+    // backends that already expire or bound their entries on their own don't need to do anything
+    async fn prune_older_than(&self, _age: Duration) -> Result<u64, StorageError> {
+        Ok(0)
+    }
+
+    // drops the oldest cached responses until each table holds at most rows of them
+    async fn prune_to_rows(&self, _rows: u64) -> Result<u64, StorageError> {
+        Ok(0)
+    }
+
+    // whether the backend can be reached, local backends always can
+    async fn ping(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    // writes out anything the backend still holds in memory, before openexecution exits.
+    // backends that write every call through right away don't need to do anything
+    async fn flush(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+// how much of the cache to keep, anything left as None is kept forever
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    pub max_age: Option<Duration>,
+    pub keep_blocks: Option<u64>, // how many blocks below the head keep their number and body
+    pub max_rows: Option<u64>,
+}
+
+impl RetentionPolicy {
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.max_age.is_none() && self.keep_blocks.is_none() && self.max_rows.is_none()
+    }
+}
+
+// applies every part of the policy once, returns how many rows or blocks were dropped in total
+pub async fn apply_retention(db: &dyn Storage, policy: &RetentionPolicy) -> Result<u64, StorageError> {
+    let mut pruned = 0;
+    if let Some(keep_blocks) = policy.keep_blocks {
+        if let Some(head) = db.head().await? {
+            let keep_from = head.saturating_sub(i64::try_from(keep_blocks).unwrap_or(i64::MAX));
+            pruned += db.prune(keep_from).await?;
+        }
+    }
+    if let Some(max_age) = policy.max_age {
+        pruned += db.prune_older_than(max_age).await?;
+    }
+    if let Some(max_rows) = policy.max_rows {
+        pruned += db.prune_to_rows(max_rows).await?;
+    }
+    Ok(pruned)
+}
+
+pub struct PostgresOptions {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub pass: String,
+    pub name: String,
+    pub pool_size: usize,
+    pub pool_timeout: Duration, // how long a request waits for a connection, and for one to be made or checked
+    pub verify_connections: bool, // run a query on a connection before handing it out again
+    pub run_migrations: bool,     // false when an operator runs `migrate` themselves with other credentials
+    pub ssl_mode: String,         // one of libpq's sslmodes, short of allow
+    pub ssl_root_cert: Option<String>,
+    pub ssl_client_cert: Option<String>, // PEM, needs ssl_client_key with it
+    pub ssl_client_key: Option<String>,
+}
+
+// the tls connector for ssl_mode, verifying as much of the server's certificate as libpq would
+fn make_tls_connector(options: &PostgresOptions) -> Result<postgres_native_tls::MakeTlsConnector, StorageError> {
+    let read = |path: &str| std::fs::read(path).map_err(|e| StorageError(format!("Unable to read {}: {}", path, e)));
+    let tls_error = |e: native_tls::Error| StorageError(format!("Unable to set up postgres tls: {}", e));
+
+    let mut builder = native_tls::TlsConnector::builder();
+    match options.ssl_mode.as_str() {
+        "verify-full" => {}
+        "verify-ca" => {
+            builder.danger_accept_invalid_hostnames(true);
+        }
+        // encrypted, but anyone can be on the other end
+        _ => {
+            builder.danger_accept_invalid_certs(true);
+            builder.danger_accept_invalid_hostnames(true);
+        }
+    }
+    if let Some(path) = &options.ssl_root_cert {
+        builder.add_root_certificate(native_tls::Certificate::from_pem(&read(path)?).map_err(tls_error)?);
+    }
+    match (&options.ssl_client_cert, &options.ssl_client_key) {
+        (Some(cert), Some(key)) => {
+            builder.identity(native_tls::Identity::from_pkcs8(&read(cert)?, &read(key)?).map_err(tls_error)?);
+        }
+        (None, None) => {}
+        _ => return Err(StorageError("a postgres client certificate needs both a cert and a key".to_owned())),
+    }
+    Ok(postgres_native_tls::MakeTlsConnector::new(builder.build().map_err(tls_error)?))
+}
+
+// the postgres schema, every file in migrations/ is applied once and in order
+mod embedded {
+    refinery::embed_migrations!("migrations");
+}
+
+// a pool of connections, so followers asking at the same time don't queue up behind one client
+pub struct PostgresStorage {
+    pool: deadpool_postgres::Pool,
+}
+
+impl PostgresStorage {
+    pub async fn connect(options: &PostgresOptions) -> Result<PostgresStorage, StorageError> {
+        let mut config = deadpool_postgres::Config::new();
+        config.host = Some(options.host.clone());
+        config.port = Some(options.port);
+        config.user = Some(options.user.clone());
+        config.password = Some(options.pass.clone());
+        config.dbname = Some(options.name.clone());
+        config.ssl_mode = Some(match options.ssl_mode.as_str() {
+            "disable" => deadpool_postgres::SslMode::Disable,
+            "prefer" => deadpool_postgres::SslMode::Prefer,
+            _ => deadpool_postgres::SslMode::Require,
+        });
+        config.manager = Some(deadpool_postgres::ManagerConfig {
+            recycling_method: match options.verify_connections {
+                true => deadpool_postgres::RecyclingMethod::Verified,
+                false => deadpool_postgres::RecyclingMethod::Fast,
+            },
+        });
+        let mut pool_config = deadpool_postgres::PoolConfig::new(options.pool_size);
+        pool_config.timeouts = deadpool_postgres::Timeouts {
+            wait: Some(options.pool_timeout),
+            create: Some(options.pool_timeout),
+            recycle: Some(options.pool_timeout),
+        };
+        config.pool = Some(pool_config);
+
+        let pool = config
+            .create_pool(Some(deadpool_postgres::Runtime::Tokio1), make_tls_connector(options)?)
+            .map_err(|e| StorageError(e.to_string()))?;
+
+        // make the first connection now, so a wrong config fails at startup and not on the first request
+        let client = pool.get().await?;
+
+        tracing::info!("Connected to postgres, pool size {}, sslmode {}", options.pool_size, options.ssl_mode);
+        drop(client);
+
+        let storage = PostgresStorage { pool };
+        if options.run_migrations {
+            storage.migrate().await?;
+        }
+        Ok(storage)
+    }
+
+    pub async fn migrate(&self) -> Result<(), StorageError> {
+        let mut client = self.pool.get().await?;
+        let report = embedded::migrations::runner().run_async(&mut **client).await?;
+        for migration in report.applied_migrations() {
+            tracing::info!("Applied migration {}", migration);
+        }
+        tracing::info!("Postgres schema is up to date");
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn get_fcu(&self, key: &str) -> Result<Option<String>, StorageError> {
+        let row = self
+            .pool
+            .get()
+            .await?
+            .query_opt("SELECT response FROM fcu WHERE request = $1;", &[&key])
+            .await?;
+        Ok(row.map(|v| v.get(0)))
+    }
+
+    async fn put_fcu(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        self.pool.get().await?.execute("INSERT INTO fcu (request, response) VALUES ($1, $2) ON CONFLICT (request) DO UPDATE SET response = $2;",
+        &[&key, &response]).await?;
+        Ok(())
+    }
+
+    async fn remove_fcu(&self, key: &str) -> Result<(), StorageError> {
+        self.pool.get().await?.execute("DELETE FROM fcu WHERE request = $1;", &[&key]).await?;
+        Ok(())
+    }
+
+    async fn get_newpayload(&self, key: &str) -> Result<Option<String>, StorageError> {
+        let row = self
+            .pool
+            .get()
+            .await?
+            .query_opt("SELECT response FROM newpayload WHERE request = $1;", &[&key])
+            .await?;
+        Ok(row.map(|v| v.get(0)))
+    }
+
+    async fn put_newpayload(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        self.pool.get().await?.execute("INSERT INTO newpayload (request, response) VALUES ($1, $2) ON CONFLICT (request) DO UPDATE SET response = $2;",
+        &[&key, &response]).await?;
+        Ok(())
+    }
+
+    async fn remove_newpayload(&self, key: &str) -> Result<(), StorageError> {
+        self.pool.get().await?.execute("DELETE FROM newpayload WHERE request = $1;", &[&key]).await?;
+        Ok(())
+    }
+
+    async fn get_exchangeconfig(&self) -> Result<Option<String>, StorageError> {
+        let row = self
+            .pool
+            .get()
+            .await?
+            .query_opt("SELECT response FROM exchangeconfig;", &[])
+            .await?;
+        Ok(row.map(|v| v.get(0)))
+    }
+
+    async fn put_exchangeconfig(&self, response: &str) -> Result<(), StorageError> {
+        let client = self.pool.get().await?;
+        client.execute("DELETE FROM exchangeconfig;", &[]).await?;
+        client
+            .execute("INSERT INTO exchangeconfig (response) VALUES ($1);", &[&response])
+            .await?;
+        Ok(())
+    }
+
+    async fn get_last_fcu(&self) -> Result<Option<String>, StorageError> {
+        let row = self.pool.get().await?.query_opt("SELECT pair FROM lastfcu;", &[]).await?;
+        Ok(row.map(|v| v.get(0)))
+    }
+
+    async fn put_last_fcu(&self, pair: &str) -> Result<(), StorageError> {
+        let client = self.pool.get().await?;
+        client.execute("DELETE FROM lastfcu;", &[]).await?;
+        client.execute("INSERT INTO lastfcu (pair) VALUES ($1);", &[&pair]).await?;
+        Ok(())
+    }
+
+    async fn get_clients(&self) -> Result<HashMap<String, String>, StorageError> {
+        let rows = self.pool.get().await?.query("SELECT name, record FROM clients;", &[]).await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    async fn put_client(&self, name: &str, record: &str) -> Result<(), StorageError> {
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "INSERT INTO clients (name, record) VALUES ($1, $2) ON CONFLICT (name) DO UPDATE SET record = EXCLUDED.record;",
+                &[&name, &record],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_bans(&self) -> Result<HashMap<String, String>, StorageError> {
+        let rows = self.pool.get().await?.query("SELECT client, record FROM bans;", &[]).await?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    async fn put_ban(&self, client: &str, record: &str) -> Result<(), StorageError> {
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "INSERT INTO bans (client, record) VALUES ($1, $2) ON CONFLICT (client) DO UPDATE SET record = EXCLUDED.record;",
+                &[&client, &record],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_ban(&self, client: &str) -> Result<(), StorageError> {
+        self.pool.get().await?.execute("DELETE FROM bans WHERE client = $1;", &[&client]).await?;
+        Ok(())
+    }
+
+    async fn get_payload_bodies(&self, block_hashes: &[String]) -> Result<HashMap<String, String>, StorageError> {
+        let rows = self
+            .pool
+            .get()
+            .await?
+            .query(
+                "SELECT blockhash, body FROM payloadbodies WHERE blockhash = ANY($1);",
+                &[&block_hashes],
+            )
+            .await?;
+        Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    async fn put_payload_body(&self, block_hash: &str, body: &str) -> Result<(), StorageError> {
+        self.pool.get().await?.execute("INSERT INTO payloadbodies (blockhash, body) VALUES ($1, $2) ON CONFLICT (blockhash) DO UPDATE SET body = $2;",
+        &[&block_hash, &body]).await?;
+        Ok(())
+    }
+
+    async fn put_payload_number(&self, block_number: i64, block_hash: &str) -> Result<(), StorageError> {
+        self.pool.get().await?.execute("INSERT INTO payloadnumbers (blocknumber, blockhash) VALUES ($1, $2) ON CONFLICT (blocknumber) DO UPDATE SET blockhash = $2;",
+        &[&block_number, &block_hash]).await?;
+        Ok(())
+    }
+
+    async fn head(&self) -> Result<Option<i64>, StorageError> {
+        let row = self
+            .pool
+            .get()
+            .await?
+            .query_one("SELECT MAX(blocknumber) FROM payloadnumbers;", &[])
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn get_payload_bodies_by_range(
+        &self,
+        start: i64,
+        end: i64,
+    ) -> Result<HashMap<i64, Option<String>>, StorageError> {
+        let rows = self
+            .pool
+            .get()
+            .await?
+            .query(
+                "SELECT n.blocknumber, b.body FROM payloadnumbers n LEFT JOIN payloadbodies b ON b.blockhash = n.blockhash WHERE n.blocknumber >= $1 AND n.blocknumber <= $2;",
+                &[&start, &end],
+            )
+            .await?;
+        Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    async fn prune(&self, keep_from: i64) -> Result<u64, StorageError> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "DELETE FROM payloadbodies WHERE blockhash IN (SELECT blockhash FROM payloadnumbers WHERE blocknumber < $1);",
+                &[&keep_from],
+            )
+            .await?;
+        let pruned = client
+            .execute("DELETE FROM payloadnumbers WHERE blocknumber < $1;", &[&keep_from])
+            .await?;
+        Ok(pruned)
+    }
+
+    async fn clear(&self, table: Table) -> Result<u64, StorageError> {
+        let client = self.pool.get().await?;
+        let table = match table {
+            Table::Fcu => "fcu",
+            Table::NewPayload => "newpayload",
+            Table::ExchangeConfig => "exchangeconfig",
+            Table::PayloadBodies => {
+                client.execute("DELETE FROM payloadnumbers;", &[]).await?;
+                "payloadbodies"
+            }
+        };
+        Ok(client.execute(&format!("DELETE FROM {};", table), &[]).await?)
+    }
+
+    async fn clear_blocks(&self, start: i64, end: i64) -> Result<u64, StorageError> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "DELETE FROM payloadbodies WHERE blockhash IN (SELECT blockhash FROM payloadnumbers WHERE blocknumber >= $1 AND blocknumber <= $2);",
+                &[&start, &end],
+            )
+            .await?;
+        let cleared = client
+            .execute("DELETE FROM payloadnumbers WHERE blocknumber >= $1 AND blocknumber <= $2;", &[&start, &end])
+            .await?;
+        Ok(cleared)
+    }
+
+    async fn prune_older_than(&self, age: Duration) -> Result<u64, StorageError> {
+        let client = self.pool.get().await?;
+        let seconds = age.as_secs_f64();
+        let mut pruned = 0;
+        for table in ["fcu", "newpayload", "payloadbodies", "payloadnumbers"] {
+            pruned += client
+                .execute(
+                    &format!("DELETE FROM {} WHERE created_at < now() - make_interval(secs => $1);", table),
+                    &[&seconds],
+                )
+                .await?;
+        }
+        Ok(pruned)
+    }
+
+    async fn prune_to_rows(&self, rows: u64) -> Result<u64, StorageError> {
+        let client = self.pool.get().await?;
+        let rows = i64::try_from(rows).unwrap_or(i64::MAX);
+        let mut pruned = 0;
+        // block numbers are kept highest first, everything else newest first
+        for (table, order) in [
+            ("fcu", "created_at"),
+            ("newpayload", "created_at"),
+            ("payloadbodies", "created_at"),
+            ("payloadnumbers", "blocknumber"),
+        ] {
+            pruned += client
+                .execute(
+                    &format!(
+                        "DELETE FROM {0} WHERE ctid IN (SELECT ctid FROM {0} ORDER BY {1} DESC OFFSET $1);",
+                        table, order
+                    ),
+                    &[&rows],
+                )
+                .await?;
+        }
+        Ok(pruned)
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.pool.get().await?.simple_query("SELECT 1;").await?;
+        Ok(())
+    }
+}
+
+// a redis backend several openexecution instances can share. with a ttl every entry expires on its own,
+// so nothing piles up the way it does in postgres
+pub struct RedisStorage {
+    conn: redis::aio::ConnectionManager,
+    ttl: Option<u64>, // seconds
+}
+
+impl RedisStorage {
+    pub async fn connect(url: &str, ttl: Option<u64>) -> Result<RedisStorage, StorageError> {
+        let client = redis::Client::open(url)?;
+        let conn = redis::aio::ConnectionManager::new(client).await?;
+
+        tracing::info!("Connected to redis");
+
+        Ok(RedisStorage { conn, ttl })
+    }
+
+    #[inline(always)]
+    async fn set(&self, key: &str, value: &str) -> Result<(), StorageError> {
+        let mut conn = self.conn.clone();
+        match self.ttl {
+            Some(ttl) => conn.set_ex::<_, _, ()>(key, value, ttl).await?,
+            None => conn.set::<_, _, ()>(key, value).await?,
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    async fn get(&self, key: &str) -> Result<Option<String>, StorageError> {
+        let mut conn = self.conn.clone();
+        Ok(conn.get(key).await?)
+    }
+
+    // drops every key matching pattern. SCAN goes through the keyspace a bit at a time, so other clients of the
+    // db aren't held up the way they would be by KEYS
+    async fn delete_matching(&self, pattern: &str) -> Result<u64, StorageError> {
+        let mut conn = self.conn.clone();
+        let keys: Vec<String> = {
+            let mut iter = conn.scan_match::<_, String>(pattern).await?;
+            let mut keys = Vec::new();
+            while let Some(v) = iter.next_item().await {
+                keys.push(v);
+            }
+            keys
+        };
+        for chunk in keys.chunks(1000) {
+            conn.del::<_, ()>(chunk).await?;
+        }
+        Ok(keys.len() as u64)
+    }
+
+    // the numbers and bodies of the blocks with numbers in the range, and their place in REDIS_NUMBERS_KEY
+    async fn delete_blocks(&self, min: String, max: String) -> Result<u64, StorageError> {
+        let mut conn = self.conn.clone();
+        let numbers: Vec<i64> = conn.zrangebyscore(REDIS_NUMBERS_KEY, &min, &max).await?;
+        if numbers.is_empty() {
+            return Ok(0);
+        }
+
+        let number_keys: Vec<String> = numbers.iter().map(|v| format!("oe:payloadnumber:{}", v)).collect();
+        let hashes: Vec<Option<String>> = redis::cmd("MGET").arg(&number_keys).query_async(&mut conn).await?;
+        let mut keys: Vec<String> = hashes
+            .into_iter()
+            .flatten()
+            .map(|v| format!("oe:payloadbody:{}", v))
+            .collect();
+        keys.extend(number_keys);
+
+        conn.del::<_, ()>(keys).await?;
+        conn.zrembyscore::<_, _, _, ()>(REDIS_NUMBERS_KEY, &min, &max).await?;
+        Ok(numbers.len() as u64)
+    }
+}
+
+// every key is prefixed so the db can be shared with other things
+const REDIS_NUMBERS_KEY: &str = "oe:payloadnumbers"; // sorted set of every stored block number, scored by itself
+const REDIS_CLIENTS_KEY: &str = "oe:clients"; // hash of follower name -> record
+const REDIS_BANS_KEY: &str = "oe:bans"; // hash of banned follower name or address -> record
+
+#[async_trait]
+impl Storage for RedisStorage {
+    async fn get_fcu(&self, key: &str) -> Result<Option<String>, StorageError> {
+        self.get(&format!("oe:fcu:{}", key)).await
+    }
+
+    async fn put_fcu(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        self.set(&format!("oe:fcu:{}", key), response).await
+    }
+
+    async fn remove_fcu(&self, key: &str) -> Result<(), StorageError> {
+        let mut conn = self.conn.clone();
+        conn.del::<_, ()>(format!("oe:fcu:{}", key)).await?;
+        Ok(())
+    }
+
+    async fn get_newpayload(&self, key: &str) -> Result<Option<String>, StorageError> {
+        self.get(&format!("oe:newpayload:{}", key)).await
+    }
+
+    async fn put_newpayload(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        self.set(&format!("oe:newpayload:{}", key), response).await
+    }
+
+    async fn remove_newpayload(&self, key: &str) -> Result<(), StorageError> {
+        let mut conn = self.conn.clone();
+        conn.del::<_, ()>(format!("oe:newpayload:{}", key)).await?;
+        Ok(())
+    }
+
+    async fn get_exchangeconfig(&self) -> Result<Option<String>, StorageError> {
+        self.get("oe:exchangeconfig").await
+    }
+
+    async fn put_exchangeconfig(&self, response: &str) -> Result<(), StorageError> {
+        self.set("oe:exchangeconfig", response).await
+    }
+
+    async fn get_last_fcu(&self) -> Result<Option<String>, StorageError> {
+        self.get("oe:lastfcu").await
+    }
+
+    async fn put_last_fcu(&self, pair: &str) -> Result<(), StorageError> {
+        self.set("oe:lastfcu", pair).await
+    }
+
+    // a hash of its own, the roster doesn't expire with the ttl
+    async fn get_clients(&self) -> Result<HashMap<String, String>, StorageError> {
+        let mut conn = self.conn.clone();
+        Ok(conn.hgetall(REDIS_CLIENTS_KEY).await?)
+    }
+
+    async fn put_client(&self, name: &str, record: &str) -> Result<(), StorageError> {
+        let mut conn = self.conn.clone();
+        conn.hset::<_, _, _, ()>(REDIS_CLIENTS_KEY, name, record).await?;
+        Ok(())
+    }
+
+    // every openexecution sharing the db has the same bans
+    async fn get_bans(&self) -> Result<HashMap<String, String>, StorageError> {
+        let mut conn = self.conn.clone();
+        Ok(conn.hgetall(REDIS_BANS_KEY).await?)
+    }
+
+    async fn put_ban(&self, client: &str, record: &str) -> Result<(), StorageError> {
+        let mut conn = self.conn.clone();
+        conn.hset::<_, _, _, ()>(REDIS_BANS_KEY, client, record).await?;
+        Ok(())
+    }
+
+    async fn remove_ban(&self, client: &str) -> Result<(), StorageError> {
+        let mut conn = self.conn.clone();
+        conn.hdel::<_, _, ()>(REDIS_BANS_KEY, client).await?;
+        Ok(())
+    }
+
+    async fn get_payload_bodies(&self, block_hashes: &[String]) -> Result<HashMap<String, String>, StorageError> {
+        if block_hashes.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let keys: Vec<String> = block_hashes.iter().map(|v| format!("oe:payloadbody:{}", v)).collect();
+        let mut conn = self.conn.clone();
+        let bodies: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+
+        Ok(block_hashes
+            .iter()
+            .zip(bodies)
+            .filter_map(|(hash, body)| body.map(|body| (hash.clone(), body)))
+            .collect())
+    }
+
+    async fn put_payload_body(&self, block_hash: &str, body: &str) -> Result<(), StorageError> {
+        self.set(&format!("oe:payloadbody:{}", block_hash), body).await
+    }
+
+    async fn put_payload_number(&self, block_number: i64, block_hash: &str) -> Result<(), StorageError> {
+        self.set(&format!("oe:payloadnumber:{}", block_number), block_hash).await?;
+        let mut conn = self.conn.clone();
+        conn.zadd::<_, _, _, ()>(REDIS_NUMBERS_KEY, block_number, block_number).await?;
+        Ok(())
+    }
+
+    async fn head(&self) -> Result<Option<i64>, StorageError> {
+        let mut conn = self.conn.clone();
+        let head: Vec<i64> = conn.zrevrange(REDIS_NUMBERS_KEY, 0, 0).await?;
+        Ok(head.first().copied())
+    }
+
+    async fn get_payload_bodies_by_range(
+        &self,
+        start: i64,
+        end: i64,
+    ) -> Result<HashMap<i64, Option<String>>, StorageError> {
+        if start > end {
+            return Ok(HashMap::new());
+        }
+
+        // numbers whose key expired are left out, like numbers we never had
+        let numbers: Vec<i64> = (start..=end).collect();
+        let keys: Vec<String> = numbers.iter().map(|v| format!("oe:payloadnumber:{}", v)).collect();
+        let mut conn = self.conn.clone();
+        let hashes: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+
+        let known: Vec<(i64, String)> = numbers
+            .into_iter()
+            .zip(hashes)
+            .filter_map(|(number, hash)| hash.map(|hash| (number, hash)))
+            .collect();
+        let block_hashes: Vec<String> = known.iter().map(|(_, hash)| hash.clone()).collect();
+        let bodies = self.get_payload_bodies(&block_hashes).await?;
+
+        Ok(known
+            .into_iter()
+            .map(|(number, hash)| (number, bodies.get(&hash).cloned()))
+            .collect())
+    }
+
+    async fn prune(&self, keep_from: i64) -> Result<u64, StorageError> {
+        self.delete_blocks("-inf".to_owned(), format!("({}", keep_from)).await
+    }
+
+    // every openexecution sharing the db loses it too
+    async fn clear(&self, table: Table) -> Result<u64, StorageError> {
+        match table {
+            Table::Fcu => self.delete_matching("oe:fcu:*").await,
+            Table::NewPayload => self.delete_matching("oe:newpayload:*").await,
+            Table::ExchangeConfig => self.delete_matching("oe:exchangeconfig").await,
+            Table::PayloadBodies => {
+                self.delete_matching("oe:payloadnumber:*").await?;
+                let mut conn = self.conn.clone();
+                conn.del::<_, ()>(REDIS_NUMBERS_KEY).await?;
+                self.delete_matching("oe:payloadbody:*").await
+            }
+        }
+    }
+
+    async fn clear_blocks(&self, start: i64, end: i64) -> Result<u64, StorageError> {
+        self.delete_blocks(start.to_string(), end.to_string()).await
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        let mut conn = self.conn.clone();
+        redis::cmd("PING").query_async::<()>(&mut conn).await?;
+        Ok(())
+    }
+}
+
+// a map that forgets its oldest entries once it holds more than capacity
+struct BoundedMap<K, V> {
+    map: HashMap<K, V>,
+    order: VecDeque<K>, // insertion order, oldest first
+    capacity: usize,
+}
+
+impl<K: Hash + Eq + Clone, V> BoundedMap<K, V> {
+    fn new(capacity: usize) -> Self {
+        BoundedMap {
+            map: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.map.insert(key.clone(), value).is_none() {
+            self.order.push_back(key);
+        }
+        while self.map.len() > self.capacity {
+            match self.order.pop_front() {
+                Some(oldest) => self.map.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        self.map.get(key)
+    }
+
+    fn remove(&mut self, key: &K) {
+        if self.map.remove(key).is_some() {
+            self.order.retain(|v| v != key);
+        }
+    }
+
+    // how many entries there were
+    fn clear(&mut self) -> u64 {
+        let cleared = self.map.len() as u64;
+        self.map.clear();
+        self.order.clear();
+        cleared
+    }
+}
+
+struct MemoryMaps {
+    fcu: BoundedMap<String, String>,
+    newpayload: BoundedMap<String, String>,
+    exchangeconfig: Option<String>,
+    last_fcu: Option<String>,
+    clients: HashMap<String, String>,
+    bans: HashMap<String, String>,
+    payloadbodies: BoundedMap<String, String>,
+    payloadnumbers: BTreeMap<i64, String>, // the lowest numbers are dropped first
+}
+
+// everything in the process, nothing to run next to openexecution but the cache is gone on restart
+pub struct MemoryStorage {
+    maps: Mutex<MemoryMaps>,
+    capacity: usize, // per map
+}
+
+impl MemoryStorage {
+    pub fn new(capacity: usize) -> MemoryStorage {
+        MemoryStorage {
+            maps: Mutex::new(MemoryMaps {
+                fcu: BoundedMap::new(capacity),
+                newpayload: BoundedMap::new(capacity),
+                exchangeconfig: None,
+                last_fcu: None,
+                clients: HashMap::new(),
+                bans: HashMap::new(),
+                payloadbodies: BoundedMap::new(capacity),
+                payloadnumbers: BTreeMap::new(),
+            }),
+            capacity,
+        }
+    }
+
+    #[inline(always)]
+    fn maps(&self) -> Result<std::sync::MutexGuard<'_, MemoryMaps>, StorageError> {
+        self.maps
+            .lock()
+            .map_err(|e| StorageError(format!("memory storage lock poisoned: {}", e)))
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn get_fcu(&self, key: &str) -> Result<Option<String>, StorageError> {
+        Ok(self.maps()?.fcu.get(&key.to_owned()).cloned())
+    }
+
+    async fn put_fcu(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        self.maps()?.fcu.insert(key.to_owned(), response.to_owned());
+        Ok(())
+    }
+
+    async fn remove_fcu(&self, key: &str) -> Result<(), StorageError> {
+        self.maps()?.fcu.remove(&key.to_owned());
+        Ok(())
+    }
+
+    async fn get_newpayload(&self, key: &str) -> Result<Option<String>, StorageError> {
+        Ok(self.maps()?.newpayload.get(&key.to_owned()).cloned())
+    }
+
+    async fn put_newpayload(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        self.maps()?.newpayload.insert(key.to_owned(), response.to_owned());
+        Ok(())
+    }
+
+    async fn remove_newpayload(&self, key: &str) -> Result<(), StorageError> {
+        self.maps()?.newpayload.remove(&key.to_owned());
+        Ok(())
+    }
+
+    async fn get_exchangeconfig(&self) -> Result<Option<String>, StorageError> {
+        Ok(self.maps()?.exchangeconfig.clone())
+    }
+
+    async fn put_exchangeconfig(&self, response: &str) -> Result<(), StorageError> {
+        self.maps()?.exchangeconfig = Some(response.to_owned());
+        Ok(())
+    }
+
+    async fn get_last_fcu(&self) -> Result<Option<String>, StorageError> {
+        Ok(self.maps()?.last_fcu.clone())
+    }
+
+    async fn put_last_fcu(&self, pair: &str) -> Result<(), StorageError> {
+        self.maps()?.last_fcu = Some(pair.to_owned());
+        Ok(())
+    }
+
+    async fn get_clients(&self) -> Result<HashMap<String, String>, StorageError> {
+        Ok(self.maps()?.clients.clone())
+    }
+
+    async fn put_client(&self, name: &str, record: &str) -> Result<(), StorageError> {
+        self.maps()?.clients.insert(name.to_owned(), record.to_owned());
+        Ok(())
+    }
+
+    async fn get_bans(&self) -> Result<HashMap<String, String>, StorageError> {
+        Ok(self.maps()?.bans.clone())
+    }
+
+    async fn put_ban(&self, client: &str, record: &str) -> Result<(), StorageError> {
+        self.maps()?.bans.insert(client.to_owned(), record.to_owned());
+        Ok(())
+    }
+
+    async fn remove_ban(&self, client: &str) -> Result<(), StorageError> {
+        self.maps()?.bans.remove(client);
+        Ok(())
+    }
+
+    async fn get_payload_bodies(&self, block_hashes: &[String]) -> Result<HashMap<String, String>, StorageError> {
+        let maps = self.maps()?;
+        Ok(block_hashes
+            .iter()
+            .filter_map(|hash| maps.payloadbodies.get(hash).map(|body| (hash.clone(), body.clone())))
+            .collect())
+    }
+
+    async fn put_payload_body(&self, block_hash: &str, body: &str) -> Result<(), StorageError> {
+        self.maps()?.payloadbodies.insert(block_hash.to_owned(), body.to_owned());
+        Ok(())
+    }
+
+    async fn put_payload_number(&self, block_number: i64, block_hash: &str) -> Result<(), StorageError> {
+        let mut maps = self.maps()?;
+        maps.payloadnumbers.insert(block_number, block_hash.to_owned());
+        while maps.payloadnumbers.len() > self.capacity {
+            maps.payloadnumbers.pop_first();
+        }
+        Ok(())
+    }
+
+    async fn head(&self) -> Result<Option<i64>, StorageError> {
+        Ok(self.maps()?.payloadnumbers.keys().next_back().copied())
+    }
+
+    async fn get_payload_bodies_by_range(
+        &self,
+        start: i64,
+        end: i64,
+    ) -> Result<HashMap<i64, Option<String>>, StorageError> {
+        if start > end {
+            return Ok(HashMap::new());
+        }
+
+        let maps = self.maps()?;
+        Ok(maps
+            .payloadnumbers
+            .range(start..=end)
+            .map(|(number, hash)| (*number, maps.payloadbodies.get(hash).cloned()))
+            .collect())
+    }
+
+    async fn prune(&self, keep_from: i64) -> Result<u64, StorageError> {
+        let mut maps = self.maps()?;
+        let kept = maps.payloadnumbers.split_off(&keep_from);
+        let pruned = std::mem::replace(&mut maps.payloadnumbers, kept);
+        for hash in pruned.values() {
+            maps.payloadbodies.remove(hash);
+        }
+        Ok(pruned.len() as u64)
+    }
+
+    async fn clear(&self, table: Table) -> Result<u64, StorageError> {
+        let mut maps = self.maps()?;
+        Ok(match table {
+            Table::Fcu => maps.fcu.clear(),
+            Table::NewPayload => maps.newpayload.clear(),
+            Table::ExchangeConfig => maps.exchangeconfig.take().map_or(0, |_| 1),
+            Table::PayloadBodies => {
+                maps.payloadnumbers.clear();
+                maps.payloadbodies.clear()
+            }
+        })
+    }
+
+    async fn clear_blocks(&self, start: i64, end: i64) -> Result<u64, StorageError> {
+        if start > end {
+            return Ok(0);
+        }
+
+        let mut maps = self.maps()?;
+        let numbers: Vec<i64> = maps.payloadnumbers.range(start..=end).map(|(number, _)| *number).collect();
+        for number in &numbers {
+            if let Some(hash) = maps.payloadnumbers.remove(number) {
+                maps.payloadbodies.remove(&hash);
+            }
+        }
+        Ok(numbers.len() as u64)
+    }
+}
+
+// an embedded kv store in a local directory, for a single host that wants the cache to survive restarts
+// without running a database. sled compacts on its own, we only keep each tree under max_entries
+pub struct SledStorage {
+    db: sled::Db,
+    fcu: sled::Tree,
+    newpayload: sled::Tree,
+    payloadbodies: sled::Tree,
+    payloadnumbers: sled::Tree, // big endian block number -> block hash, so iteration is in block order
+    order: sled::Tree,          // big endian insertion id -> tree name and key, oldest first
+    clients: sled::Tree,        // follower name -> record, not bounded by max_entries
+    bans: sled::Tree,           // banned follower name or address -> record, not bounded either
+    max_entries: u64,
+}
+
+impl SledStorage {
+    pub fn open(path: &str, max_entries: u64) -> Result<SledStorage, StorageError> {
+        let db = sled::open(path)?;
+
+        tracing::info!("Opened sled db at {}", path);
+
+        Ok(SledStorage {
+            fcu: db.open_tree("fcu")?,
+            newpayload: db.open_tree("newpayload")?,
+            payloadbodies: db.open_tree("payloadbodies")?,
+            payloadnumbers: db.open_tree("payloadnumbers")?,
+            order: db.open_tree("order")?,
+            clients: db.open_tree("clients")?,
+            bans: db.open_tree("bans")?,
+            db,
+            max_entries,
+        })
+    }
+
+    #[inline(always)]
+    fn tree(&self, name: &[u8]) -> Option<&sled::Tree> {
+        match name {
+            b"fcu" => Some(&self.fcu),
+            b"newpayload" => Some(&self.newpayload),
+            b"payloadbodies" => Some(&self.payloadbodies),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    fn insert_bounded(&self, name: &[u8], key: &str, value: &str) -> Result<(), StorageError> {
+        let tree = match self.tree(name) {
+            Some(v) => v,
+            None => return Err(StorageError(format!("no sled tree {}", String::from_utf8_lossy(name)))),
+        };
+        tree.insert(key, value)?;
+
+        // remember when it went in, then drop whatever fell out of the window.
+        // the window is shared by the three trees, so together they stay under 3 * max_entries
+        let id = self.db.generate_id()?;
+        self.order.insert(id.to_be_bytes(), [name, b":", key.as_bytes()].concat())?;
+        let window = self.max_entries.saturating_mul(3);
+        if id >= window {
+            for entry in self.order.range(..(id - window).to_be_bytes()) {
+                let (order_key, entry) = entry?;
+                if let Some(index) = entry.iter().position(|v| *v == b':') {
+                    if let Some(tree) = self.tree(&entry[..index]) {
+                        tree.remove(&entry[index + 1..])?;
+                    }
+                }
+                self.order.remove(order_key)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn get_string(tree: &sled::Tree, key: &[u8]) -> Result<Option<String>, StorageError> {
+        Ok(tree.get(key)?.map(|v| String::from_utf8_lossy(&v).to_string()))
+    }
+}
+
+#[async_trait]
+impl Storage for SledStorage {
+    async fn get_fcu(&self, key: &str) -> Result<Option<String>, StorageError> {
+        SledStorage::get_string(&self.fcu, key.as_bytes())
+    }
+
+    async fn put_fcu(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        self.insert_bounded(b"fcu", key, response)
+    }
+
+    // its entry in order is left to age out, removing a key that's gone already does nothing
+    async fn remove_fcu(&self, key: &str) -> Result<(), StorageError> {
+        self.fcu.remove(key)?;
+        Ok(())
+    }
+
+    async fn get_newpayload(&self, key: &str) -> Result<Option<String>, StorageError> {
+        SledStorage::get_string(&self.newpayload, key.as_bytes())
+    }
+
+    async fn put_newpayload(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        self.insert_bounded(b"newpayload", key, response)
+    }
+
+    async fn remove_newpayload(&self, key: &str) -> Result<(), StorageError> {
+        self.newpayload.remove(key)?;
+        Ok(())
+    }
+
+    async fn get_exchangeconfig(&self) -> Result<Option<String>, StorageError> {
+        SledStorage::get_string(&self.db, b"exchangeconfig")
+    }
+
+    async fn put_exchangeconfig(&self, response: &str) -> Result<(), StorageError> {
+        self.db.insert(b"exchangeconfig", response.as_bytes())?;
+        Ok(())
+    }
+
+    async fn get_last_fcu(&self) -> Result<Option<String>, StorageError> {
+        SledStorage::get_string(&self.db, b"lastfcu")
+    }
+
+    async fn put_last_fcu(&self, pair: &str) -> Result<(), StorageError> {
+        self.db.insert(b"lastfcu", pair.as_bytes())?;
+        Ok(())
+    }
+
+    async fn get_clients(&self) -> Result<HashMap<String, String>, StorageError> {
+        let mut clients = HashMap::new();
+        for entry in self.clients.iter() {
+            let (name, record) = entry?;
+            clients.insert(String::from_utf8_lossy(&name).to_string(), String::from_utf8_lossy(&record).to_string());
+        }
+        Ok(clients)
+    }
+
+    async fn put_client(&self, name: &str, record: &str) -> Result<(), StorageError> {
+        self.clients.insert(name, record)?;
+        Ok(())
+    }
+
+    async fn get_bans(&self) -> Result<HashMap<String, String>, StorageError> {
+        let mut bans = HashMap::new();
+        for entry in self.bans.iter() {
+            let (client, record) = entry?;
+            bans.insert(String::from_utf8_lossy(&client).to_string(), String::from_utf8_lossy(&record).to_string());
+        }
+        Ok(bans)
+    }
+
+    async fn put_ban(&self, client: &str, record: &str) -> Result<(), StorageError> {
+        self.bans.insert(client, record)?;
+        Ok(())
+    }
+
+    async fn remove_ban(&self, client: &str) -> Result<(), StorageError> {
+        self.bans.remove(client)?;
+        Ok(())
+    }
+
+    async fn get_payload_bodies(&self, block_hashes: &[String]) -> Result<HashMap<String, String>, StorageError> {
+        let mut bodies = HashMap::new();
+        for hash in block_hashes {
+            if let Some(body) = SledStorage::get_string(&self.payloadbodies, hash.as_bytes())? {
+                bodies.insert(hash.clone(), body);
+            }
+        }
+        Ok(bodies)
+    }
+
+    async fn put_payload_body(&self, block_hash: &str, body: &str) -> Result<(), StorageError> {
+        self.insert_bounded(b"payloadbodies", block_hash, body)
+    }
+
+    async fn put_payload_number(&self, block_number: i64, block_hash: &str) -> Result<(), StorageError> {
+        self.payloadnumbers.insert(block_number.to_be_bytes(), block_hash.as_bytes())?;
+        // numbers are dropped lowest first, the blocks near the head are the ones that get asked for
+        let keep_from = block_number.saturating_sub(i64::try_from(self.max_entries).unwrap_or(i64::MAX));
+        for entry in self.payloadnumbers.range(..keep_from.max(0).to_be_bytes()) {
+            let (number, _) = entry?;
+            self.payloadnumbers.remove(number)?;
+        }
+        Ok(())
+    }
+
+    async fn head(&self) -> Result<Option<i64>, StorageError> {
+        match self.payloadnumbers.last()? {
+            Some((number, _)) => Ok(Some(i64::from_be_bytes(
+                number.as_ref().try_into().map_err(|_| StorageError("bad block number key in sled".to_owned()))?,
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_payload_bodies_by_range(
+        &self,
+        start: i64,
+        end: i64,
+    ) -> Result<HashMap<i64, Option<String>>, StorageError> {
+        let mut bodies = HashMap::new();
+        if start > end {
+            return Ok(bodies);
+        }
+
+        for entry in self.payloadnumbers.range(start.to_be_bytes()..=end.to_be_bytes()) {
+            let (number, hash) = entry?;
+            let number = i64::from_be_bytes(
+                number.as_ref().try_into().map_err(|_| StorageError("bad block number key in sled".to_owned()))?,
+            );
+            bodies.insert(number, SledStorage::get_string(&self.payloadbodies, &hash)?);
+        }
+        Ok(bodies)
+    }
+
+    async fn prune(&self, keep_from: i64) -> Result<u64, StorageError> {
+        let mut pruned = 0;
+        for entry in self.payloadnumbers.range(..keep_from.max(0).to_be_bytes()) {
+            let (number, hash) = entry?;
+            self.payloadbodies.remove(&hash)?;
+            self.payloadnumbers.remove(number)?;
+            pruned += 1;
+        }
+        Ok(pruned)
+    }
+
+    // the entries in order are left to age out, like remove_fcu's
+    async fn clear(&self, table: Table) -> Result<u64, StorageError> {
+        let tree = match table {
+            Table::Fcu => &self.fcu,
+            Table::NewPayload => &self.newpayload,
+            Table::ExchangeConfig => return Ok(self.db.remove(b"exchangeconfig")?.map_or(0, |_| 1)),
+            Table::PayloadBodies => {
+                self.payloadnumbers.clear()?;
+                &self.payloadbodies
+            }
+        };
+        let cleared = tree.len() as u64;
+        tree.clear()?;
+        Ok(cleared)
+    }
+
+    async fn clear_blocks(&self, start: i64, end: i64) -> Result<u64, StorageError> {
+        let mut cleared = 0;
+        if start > end || end < 0 {
+            return Ok(cleared);
+        }
+
+        for entry in self.payloadnumbers.range(start.max(0).to_be_bytes()..=end.to_be_bytes()) {
+            let (number, hash) = entry?;
+            self.payloadbodies.remove(&hash)?;
+            self.payloadnumbers.remove(number)?;
+            cleared += 1;
+        }
+        Ok(cleared)
+    }
+
+    // sled only writes to disk every so often on its own
+    async fn flush(&self) -> Result<(), StorageError> {
+        self.db.flush_async().await?;
+        Ok(())
+    }
+}
+
+// first wait before trying a lost backend again, doubled after every failed try up to the max
+const RECONNECT_BACKOFF_START: Duration = Duration::from_millis(500);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+// puts a networked backend in front of an in-memory one. once the backend fails, followers are answered
+// from memory while a task pings it with backoff, and the backend is used again as soon as it answers.
+// what the canonical CL sends in the meantime only lands in memory, so it's gone once the backend is back
+pub struct SupervisedStorage {
+    name: &'static str,
+    primary: Arc<dyn Storage>,
+    fallback: MemoryStorage,
+    healthy: Arc<AtomicBool>,
+}
+
+impl SupervisedStorage {
+    pub fn new(name: &'static str, primary: Arc<dyn Storage>, fallback_capacity: usize) -> SupervisedStorage {
+        SupervisedStorage {
+            name,
+            primary,
+            fallback: MemoryStorage::new(fallback_capacity),
+            healthy: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    // only the call that flips healthy starts the reconnect task, so there's only ever one
+    fn degrade(&self, e: &StorageError) {
+        if !self.healthy.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        tracing::error!("Lost {}, answering from memory until it's back: {}", self.name, e);
+
+        let name = self.name;
+        let primary = self.primary.clone();
+        let healthy = self.healthy.clone();
+        tokio::spawn(async move {
+            let mut backoff = RECONNECT_BACKOFF_START;
+            loop {
+                tokio::time::sleep(backoff).await;
+                match primary.ping().await {
+                    Ok(()) => {
+                        tracing::info!("Reconnected to {}", name);
+                        healthy.store(true, Ordering::SeqCst);
+                        return;
+                    }
+                    Err(e) => {
+                        backoff = (backoff * 2).min(RECONNECT_BACKOFF_MAX);
+                        tracing::warn!("Still unable to reach {}, next try in {:?}: {}", name, backoff, e);
+                    }
+                }
+            }
+        });
+    }
+}
+
+// tries the backend while it's healthy, and falls through to memory when it isn't or the call fails
+macro_rules! supervised {
+    ($self:ident, $method:ident($($arg:expr),*)) => {{
+        if $self.healthy.load(Ordering::SeqCst) {
+            match $self.primary.$method($($arg),*).await {
+                Ok(v) => return Ok(v),
+                Err(e) => $self.degrade(&e),
+            }
+        }
+        $self.fallback.$method($($arg),*).await
+    }};
+}
+
+#[async_trait]
+impl Storage for SupervisedStorage {
+    async fn get_fcu(&self, key: &str) -> Result<Option<String>, StorageError> {
+        supervised!(self, get_fcu(key))
+    }
+
+    async fn put_fcu(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        supervised!(self, put_fcu(key, response))
+    }
+
+    async fn remove_fcu(&self, key: &str) -> Result<(), StorageError> {
+        supervised!(self, remove_fcu(key))
+    }
+
+    async fn get_newpayload(&self, key: &str) -> Result<Option<String>, StorageError> {
+        supervised!(self, get_newpayload(key))
+    }
+
+    async fn put_newpayload(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        supervised!(self, put_newpayload(key, response))
+    }
+
+    async fn remove_newpayload(&self, key: &str) -> Result<(), StorageError> {
+        supervised!(self, remove_newpayload(key))
+    }
+
+    async fn get_exchangeconfig(&self) -> Result<Option<String>, StorageError> {
+        supervised!(self, get_exchangeconfig())
+    }
+
+    async fn put_exchangeconfig(&self, response: &str) -> Result<(), StorageError> {
+        supervised!(self, put_exchangeconfig(response))
+    }
+
+    async fn get_last_fcu(&self) -> Result<Option<String>, StorageError> {
+        supervised!(self, get_last_fcu())
+    }
+
+    async fn put_last_fcu(&self, pair: &str) -> Result<(), StorageError> {
+        supervised!(self, put_last_fcu(pair))
+    }
+
+    async fn get_clients(&self) -> Result<HashMap<String, String>, StorageError> {
+        supervised!(self, get_clients())
+    }
+
+    async fn put_client(&self, name: &str, record: &str) -> Result<(), StorageError> {
+        supervised!(self, put_client(name, record))
+    }
+
+    async fn get_bans(&self) -> Result<HashMap<String, String>, StorageError> {
+        supervised!(self, get_bans())
+    }
+
+    async fn put_ban(&self, client: &str, record: &str) -> Result<(), StorageError> {
+        supervised!(self, put_ban(client, record))
+    }
+
+    async fn remove_ban(&self, client: &str) -> Result<(), StorageError> {
+        supervised!(self, remove_ban(client))
+    }
+
+    async fn get_payload_bodies(&self, block_hashes: &[String]) -> Result<HashMap<String, String>, StorageError> {
+        supervised!(self, get_payload_bodies(block_hashes))
+    }
+
+    async fn put_payload_body(&self, block_hash: &str, body: &str) -> Result<(), StorageError> {
+        supervised!(self, put_payload_body(block_hash, body))
+    }
+
+    async fn put_payload_number(&self, block_number: i64, block_hash: &str) -> Result<(), StorageError> {
+        supervised!(self, put_payload_number(block_number, block_hash))
+    }
+
+    async fn head(&self) -> Result<Option<i64>, StorageError> {
+        supervised!(self, head())
+    }
+
+    async fn get_payload_bodies_by_range(
+        &self,
+        start: i64,
+        end: i64,
+    ) -> Result<HashMap<i64, Option<String>>, StorageError> {
+        supervised!(self, get_payload_bodies_by_range(start, end))
+    }
+
+    async fn prune(&self, keep_from: i64) -> Result<u64, StorageError> {
+        supervised!(self, prune(keep_from))
+    }
+
+    // the memory's too, what it has from while the backend was gone is answered from the next time it is
+    async fn clear(&self, table: Table) -> Result<u64, StorageError> {
+        self.fallback.clear(table).await?;
+        supervised!(self, clear(table))
+    }
+
+    async fn clear_blocks(&self, start: i64, end: i64) -> Result<u64, StorageError> {
+        self.fallback.clear_blocks(start, end).await?;
+        supervised!(self, clear_blocks(start, end))
+    }
+
+    async fn prune_older_than(&self, age: Duration) -> Result<u64, StorageError> {
+        supervised!(self, prune_older_than(age))
+    }
+
+    async fn prune_to_rows(&self, rows: u64) -> Result<u64, StorageError> {
+        supervised!(self, prune_to_rows(rows))
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.primary.ping().await
+    }
+
+    async fn flush(&self) -> Result<(), StorageError> {
+        self.primary.flush().await
+    }
+}
+
+// a response kept in memory, until when the backend keeps it too
+#[derive(Clone)]
+struct Cached {
+    value: String,
+    until: Option<Instant>,
+}
+
+// payload bodies are weighed by their length, a block's transactions can be megabytes
+#[derive(Clone)]
+struct ByLength;
+
+impl quick_cache::Weighter<String, Cached> for ByLength {
+    #[inline(always)]
+    fn weight(&self, key: &String, val: &Cached) -> u64 {
+        (key.len() + val.value.len()) as u64
+    }
+}
+
+// what most blocks' transactions come to, for how many bodies the cache expects to hold
+const TYPICAL_BODY_SIZE: u64 = 256 * 1024;
+
+// the response when it's there and the backend hasn't expired it yet
+#[inline(always)]
+fn lookup<W: quick_cache::Weighter<String, Cached> + Clone>(
+    cache: &quick_cache::sync::Cache<String, Cached, W>,
+    key: &str,
+) -> Option<String> {
+    let cached = cache.get(key)?;
+    if cached.until.is_some_and(|v| v <= Instant::now()) {
+        cache.remove(key);
+        return None;
+    }
+    Some(cached.value)
+}
+
+// the last fcU, newPayload and payload body responses in memory in front of a networked backend, looked up before
+// it, so followers on the head are answered in microseconds rather than a round trip. only writes fill it, a lookup
+// the backend answers isn't kept, so nothing's in it for longer than the backend has it: an entry goes after the
+// backend's ttl, and what retention prunes from the backend is cleared from it
+pub struct CachedStorage {
+    inner: Arc<dyn Storage>,
+    ttl: Option<Duration>, // redis's --cache-ttl
+    fcu: quick_cache::sync::Cache<String, Cached>,
+    newpayload: quick_cache::sync::Cache<String, Cached>,
+    payloadbodies: quick_cache::sync::Cache<String, Cached, ByLength>, // by block hash, bounded in bytes
+}
+
+impl CachedStorage {
+    // entries of fcU and of newPayload responses each, bodies_size bytes of payload bodies
+    pub fn new(inner: Arc<dyn Storage>, entries: usize, bodies_size: u64, ttl: Option<Duration>) -> CachedStorage {
+        let bodies = usize::try_from(bodies_size / TYPICAL_BODY_SIZE).unwrap_or(usize::MAX).max(1);
+        CachedStorage {
+            inner,
+            ttl,
+            fcu: quick_cache::sync::Cache::new(entries.max(1)),
+            newpayload: quick_cache::sync::Cache::new(entries.max(1)),
+            payloadbodies: quick_cache::sync::Cache::with_weighter(bodies, bodies_size, ByLength),
+        }
+    }
+
+    #[inline(always)]
+    fn cached(&self, value: &str) -> Cached {
+        Cached {
+            value: value.to_owned(),
+            until: self.ttl.map(|v| Instant::now() + v),
+        }
+    }
+
+    #[inline(always)]
+    fn clear_all(&self) {
+        self.fcu.clear();
+        self.newpayload.clear();
+        self.payloadbodies.clear();
+    }
+}
+
+#[async_trait]
+impl Storage for CachedStorage {
+    async fn get_fcu(&self, key: &str) -> Result<Option<String>, StorageError> {
+        match lookup(&self.fcu, key) {
+            Some(v) => Ok(Some(v)),
+            None => self.inner.get_fcu(key).await,
+        }
+    }
+
+    async fn put_fcu(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        self.inner.put_fcu(key, response).await?;
+        self.fcu.insert(key.to_owned(), self.cached(response));
+        Ok(())
+    }
+
+    async fn remove_fcu(&self, key: &str) -> Result<(), StorageError> {
+        self.fcu.remove(key);
+        self.inner.remove_fcu(key).await
+    }
+
+    async fn get_newpayload(&self, key: &str) -> Result<Option<String>, StorageError> {
+        match lookup(&self.newpayload, key) {
+            Some(v) => Ok(Some(v)),
+            None => self.inner.get_newpayload(key).await,
+        }
+    }
+
+    async fn put_newpayload(&self, key: &str, response: &str) -> Result<(), StorageError> {
+        self.inner.put_newpayload(key, response).await?;
+        self.newpayload.insert(key.to_owned(), self.cached(response));
+        Ok(())
+    }
+
+    async fn remove_newpayload(&self, key: &str) -> Result<(), StorageError> {
+        self.newpayload.remove(key);
+        self.inner.remove_newpayload(key).await
+    }
+
+    async fn get_exchangeconfig(&self) -> Result<Option<String>, StorageError> {
+        self.inner.get_exchangeconfig().await
+    }
+
+    async fn put_exchangeconfig(&self, response: &str) -> Result<(), StorageError> {
+        self.inner.put_exchangeconfig(response).await
+    }
+
+    async fn get_last_fcu(&self) -> Result<Option<String>, StorageError> {
+        self.inner.get_last_fcu().await
+    }
+
+    async fn put_last_fcu(&self, pair: &str) -> Result<(), StorageError> {
+        self.inner.put_last_fcu(pair).await
+    }
+
+    async fn get_clients(&self) -> Result<HashMap<String, String>, StorageError> {
+        self.inner.get_clients().await
+    }
+
+    async fn put_client(&self, name: &str, record: &str) -> Result<(), StorageError> {
+        self.inner.put_client(name, record).await
+    }
+
+    async fn get_bans(&self) -> Result<HashMap<String, String>, StorageError> {
+        self.inner.get_bans().await
+    }
+
+    async fn put_ban(&self, client: &str, record: &str) -> Result<(), StorageError> {
+        self.inner.put_ban(client, record).await
+    }
+
+    async fn remove_ban(&self, client: &str) -> Result<(), StorageError> {
+        self.inner.remove_ban(client).await
+    }
+
+    // only the hashes that aren't in memory go to the backend
+    async fn get_payload_bodies(&self, block_hashes: &[String]) -> Result<HashMap<String, String>, StorageError> {
+        let mut bodies = HashMap::new();
+        let mut missing = Vec::new();
+        for hash in block_hashes {
+            match lookup(&self.payloadbodies, hash) {
+                Some(v) => {
+                    bodies.insert(hash.clone(), v);
+                }
+                None => missing.push(hash.clone()),
+            }
+        }
+        if !missing.is_empty() {
+            bodies.extend(self.inner.get_payload_bodies(&missing).await?);
+        }
+        Ok(bodies)
+    }
+
+    async fn put_payload_body(&self, block_hash: &str, body: &str) -> Result<(), StorageError> {
+        self.inner.put_payload_body(block_hash, body).await?;
+        self.payloadbodies.insert(block_hash.to_owned(), self.cached(body));
+        Ok(())
+    }
+
+    async fn put_payload_number(&self, block_number: i64, block_hash: &str) -> Result<(), StorageError> {
+        self.inner.put_payload_number(block_number, block_hash).await
+    }
+
+    async fn head(&self) -> Result<Option<i64>, StorageError> {
+        self.inner.head().await
+    }
+
+    async fn get_payload_bodies_by_range(
+        &self,
+        start: i64,
+        end: i64,
+    ) -> Result<HashMap<i64, Option<String>>, StorageError> {
+        self.inner.get_payload_bodies_by_range(start, end).await
+    }
+
+    // the bodies in memory aren't kept by number, so all of them go once the backend pruned any
+    async fn prune(&self, keep_from: i64) -> Result<u64, StorageError> {
+        let pruned = self.inner.prune(keep_from).await?;
+        if pruned > 0 {
+            self.payloadbodies.clear();
+        }
+        Ok(pruned)
+    }
+
+    async fn clear(&self, table: Table) -> Result<u64, StorageError> {
+        match table {
+            Table::Fcu => self.fcu.clear(),
+            Table::NewPayload => self.newpayload.clear(),
+            Table::ExchangeConfig => {}
+            Table::PayloadBodies => self.payloadbodies.clear(),
+        }
+        self.inner.clear(table).await
+    }
+
+    // the bodies in memory aren't kept by number, so all of them go
+    async fn clear_blocks(&self, start: i64, end: i64) -> Result<u64, StorageError> {
+        self.payloadbodies.clear();
+        self.inner.clear_blocks(start, end).await
+    }
+
+    // which rows went isn't known here, so everything does once any did
+    async fn prune_older_than(&self, age: Duration) -> Result<u64, StorageError> {
+        let pruned = self.inner.prune_older_than(age).await?;
+        if pruned > 0 {
+            self.clear_all();
+        }
+        Ok(pruned)
+    }
+
+    async fn prune_to_rows(&self, rows: u64) -> Result<u64, StorageError> {
+        let pruned = self.inner.prune_to_rows(rows).await?;
+        if pruned > 0 {
+            self.clear_all();
+        }
+        Ok(pruned)
+    }
+
+    async fn ping(&self) -> Result<(), StorageError> {
+        self.inner.ping().await
+    }
+
+    async fn flush(&self) -> Result<(), StorageError> {
+        self.inner.flush().await
+    }
+}
@@ -0,0 +1,97 @@
+// eth_subscribe("newHeads") on the followers' websockets, the header of every block the controlling CL's fcUs make
+// the head, from the payload OE was sent for it, so monitoring can follow the head through OE without the EL
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+};
+
+// how many heads a subscriber can fall behind by before it misses some
+const NEW_HEADS_BACKLOG: usize = 16;
+
+pub struct NewHeads(broadcast::Sender<Arc<serde_json::Value>>);
+
+impl NewHeads {
+    pub fn new() -> NewHeads {
+        NewHeads(broadcast::channel(NEW_HEADS_BACKLOG).0)
+    }
+
+    // a block as eth_getBlockByHash answers with it, the fields that aren't the header's are left out
+    pub fn publish(&self, block: &serde_json::Value) {
+        let mut header = block.clone();
+        if let Some(v) = header.as_object_mut() {
+            for field in ["transactions", "withdrawals", "uncles", "size"] {
+                v.remove(field);
+            }
+        }
+        // nobody having subscribed is the usual case
+        let _ = self.0.send(Arc::new(header));
+    }
+}
+
+// the subscriptions of one websocket, their notifications go out through outbox. they end with it
+pub struct Subscriptions {
+    outbox: mpsc::UnboundedSender<String>,
+    tasks: Mutex<HashMap<String, JoinHandle<()>>>, // by subscription id
+}
+
+impl Subscriptions {
+    pub fn new(outbox: mpsc::UnboundedSender<String>) -> Subscriptions {
+        Subscriptions {
+            outbox,
+            tasks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // the id of the subscription
+    pub fn new_heads(&self, new_heads: &NewHeads) -> String {
+        let id = format!("0x{}", hex::encode(rand::random::<[u8; 16]>()));
+        let mut heads = new_heads.0.subscribe();
+        let outbox = self.outbox.clone();
+        let subscription = id.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let header = match heads.recv().await {
+                    Ok(v) => v,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("newHeads subscription {} fell {} heads behind, they're skipped", subscription, n);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                let notification = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "eth_subscription",
+                    "params": { "subscription": subscription, "result": *header },
+                });
+                if outbox.send(notification.to_string()).is_err() {
+                    return;
+                }
+            }
+        });
+        self.tasks.lock().unwrap().insert(id.clone(), task);
+        id
+    }
+
+    // whether there was a subscription with the id
+    pub fn unsubscribe(&self, id: &str) -> bool {
+        match self.tasks.lock().unwrap().remove(id) {
+            Some(v) => {
+                v.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        for task in self.tasks.lock().unwrap().values() {
+            task.abort();
+        }
+    }
+}
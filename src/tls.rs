@@ -0,0 +1,251 @@
+// https for the CLs that connect to openexecution, and the client certificates followers can sign in with instead of a jwt
+use axum::extract::connect_info::Connected;
+use hyper::server::conn::AddrStream;
+use std::{
+    io::BufReader,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+    sync::RwLock,
+};
+use tokio_rustls::{
+    rustls::{server::AllowAnyAnonymousOrAuthenticatedClient, Certificate, PrivateKey, RootCertStore, ServerConfig},
+    server::TlsStream,
+    TlsAcceptor,
+};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+// a client that doesn't finish its handshake in this long is dropped, it shouldn't hold a task forever
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// how often the certificate files are checked for a renewal, a SIGHUP checks them right away
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+// who is on the other end of a connection, as much as the connection itself tells
+#[derive(Debug, Clone)]
+pub struct Peer {
+    pub addr: SocketAddr,
+    pub cert_names: Arc<Vec<String>>, // the CN and SANs of a client certificate --tls-client-ca signed, empty without one
+}
+
+impl Connected<&AddrStream> for Peer {
+    fn connect_info(target: &AddrStream) -> Self {
+        Peer {
+            addr: target.remote_addr(),
+            cert_names: Default::default(),
+        }
+    }
+}
+
+impl Connected<&TlsConnection> for Peer {
+    fn connect_info(target: &TlsConnection) -> Self {
+        target.peer.clone()
+    }
+}
+
+#[inline(always)]
+fn read_pem(path: &str) -> Result<Vec<rustls_pemfile::Item>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+    rustls_pemfile::read_all(&mut BufReader::new(file)).map_err(|e| format!("Unable to decode {}: {}", path, e))
+}
+
+#[inline(always)]
+fn read_certs(path: &str) -> Result<Vec<Certificate>, String> {
+    let certs: Vec<Certificate> = read_pem(path)?
+        .into_iter()
+        .filter_map(|item| match item {
+            rustls_pemfile::Item::X509Certificate(v) => Some(Certificate(v)),
+            _ => None,
+        })
+        .collect();
+    match certs.is_empty() {
+        true => Err(format!("{} has no certificates in it", path)),
+        false => Ok(certs),
+    }
+}
+
+#[inline(always)]
+fn read_key(path: &str) -> Result<PrivateKey, String> {
+    read_pem(path)?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(v) | rustls_pemfile::Item::RSAKey(v) | rustls_pemfile::Item::ECKey(v) => {
+                Some(PrivateKey(v))
+            }
+            _ => None,
+        })
+        .ok_or_else(|| format!("{} has no private key in it", path))
+}
+
+// the server side of openexecution's tls. with a client CA, clients may present a certificate it signed,
+// the ones that don't are still let in and have to authenticate like they would over plain http
+pub fn load_config(cert_path: &str, key_path: &str, client_ca_path: Option<&str>) -> Result<Arc<ServerConfig>, String> {
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(path)? {
+                roots.add(&cert).map_err(|e| format!("Unable to use {}: {}", path, e))?;
+            }
+            builder.with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed())
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let config = builder
+        .with_single_cert(read_certs(cert_path)?, read_key(key_path)?)
+        .map_err(|e| format!("Unable to use {}: {}", cert_path, e))?;
+    Ok(Arc::new(config))
+}
+
+// the names a client certificate goes by, its subject's CN and its DNS, email and URI SANs
+fn cert_names(cert: &Certificate) -> Vec<String> {
+    let cert = match X509Certificate::from_der(&cert.0) {
+        Ok((_, v)) => v,
+        Err(e) => {
+            tracing::warn!("Unable to parse a client certificate: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut names: Vec<String> = cert
+        .subject()
+        .iter_common_name()
+        .filter_map(|v| v.as_str().ok())
+        .map(|v| v.to_owned())
+        .collect();
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        for name in &san.value.general_names {
+            match name {
+                GeneralName::DNSName(v) | GeneralName::RFC822Name(v) | GeneralName::URI(v) => names.push(v.to_string()),
+                _ => {}
+            }
+        }
+    }
+    names
+}
+
+// a tls connection hyper can serve, with what its handshake said about the client
+pub struct TlsConnection {
+    stream: TlsStream<TcpStream>,
+    peer: Peer,
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+async fn handshake(acceptor: TlsAcceptor, stream: TcpStream, addr: SocketAddr) -> Result<TlsConnection, String> {
+    let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+        Ok(Ok(v)) => v,
+        Ok(Err(e)) => return Err(e.to_string()),
+        Err(_) => return Err("timed out".to_owned()),
+    };
+    let cert_names = stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|v| v.first())
+        .map(cert_names)
+        .unwrap_or_default();
+    Ok(TlsConnection {
+        stream,
+        peer: Peer {
+            addr,
+            cert_names: Arc::new(cert_names),
+        },
+    })
+}
+
+// the connections to listener that made it through a handshake, each handshake runs on its own
+// so a slow client doesn't hold up the ones behind it. every handshake uses the config as it is then
+pub fn incoming(
+    listener: TcpListener,
+    config: Arc<RwLock<Arc<ServerConfig>>>,
+) -> impl hyper::server::accept::Accept<Conn = TlsConnection, Error = std::io::Error> {
+    let (tx, rx) = tokio::sync::mpsc::channel(64);
+    tokio::spawn(async move {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(v) => v,
+                Err(e) => {
+                    tracing::error!("Unable to accept a connection: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let acceptor = TlsAcceptor::from(config.read().await.clone());
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                match handshake(acceptor, stream, addr).await {
+                    Ok(v) => {
+                        let _ = tx.send(v).await;
+                    }
+                    Err(e) => tracing::debug!("TLS handshake with {} failed: {}", addr, e),
+                }
+            });
+        }
+    });
+
+    hyper::server::accept::from_stream(futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|v| (Ok(v), rx))
+    }))
+}
+
+// swaps in the certificate when its files change, so a renewed one is served without a restart.
+// files that don't load keep the certificate from before them, a renewal is often the cert and the key one after the other
+pub fn spawn_config_reloader(cert_path: String, key_path: String, client_ca_path: Option<String>, config: Arc<RwLock<Arc<ServerConfig>>>) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+        #[cfg(not(unix))]
+        let mut hangup = None;
+
+        let modified = || {
+            (
+                crate::auth::modified(&cert_path),
+                crate::auth::modified(&key_path),
+                client_ca_path.as_deref().and_then(crate::auth::modified),
+            )
+        };
+        let mut interval = tokio::time::interval(RELOAD_POLL_INTERVAL);
+        let mut last_modified = modified();
+        loop {
+            let forced = crate::auth::wait_for_check(&mut interval, &mut hangup).await;
+
+            let now_modified = modified();
+            if !forced && now_modified == last_modified {
+                continue;
+            }
+            last_modified = now_modified;
+            match load_config(&cert_path, &key_path, client_ca_path.as_deref()) {
+                Ok(v) => {
+                    *config.write().await = v;
+                    tracing::info!("Reloaded the TLS certificate");
+                }
+                Err(e) => tracing::error!("Unable to reload the TLS certificate, keeping the old one: {}", e),
+            }
+        }
+    });
+}
@@ -2,58 +2,337 @@
 #![allow(non_camel_case_types)]
 #![allow(dead_code)]
 
+use crate::blockhash;
+use crate::primitives::{Address, Bloom, Bytes, H256, U256, U64};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
 use std::{
+    collections::HashMap,
     error::Error,
-    io,
+    fmt, io,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 use tokio::sync::RwLock;
 use tracing_subscriber::fmt::writer::MakeWriter;
 
+// JSON-RPC ids can be numbers, strings or null, and the reply has to use the same one
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum RpcId {
+    Number(serde_json::Number),
+    String(String),
+    Null,
+}
+
+impl Default for RpcId {
+    // what the id is set to before something is cached
+    fn default() -> Self {
+        RpcId::Number(0.into())
+    }
+}
+
+impl fmt::Display for RpcId {
+    // the JSON representation, so it can go straight into a hand made response
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcId::Number(v) => write!(f, "{}", v),
+            RpcId::String(v) => write!(f, "{}", serde_json::Value::String(v.clone())),
+            RpcId::Null => write!(f, "null"),
+        }
+    }
+}
+
+// the parts of a request the router looks at, params are kept as raw JSON so nothing big gets decoded here
+#[derive(Debug, Deserialize)]
+pub struct RpcEnvelope<'a> {
+    #[serde(default)]
+    pub id: serde_json::Value,
+    pub method: Option<serde_json::Value>,
+    #[serde(borrow)]
+    pub params: Option<&'a serde_json::value::RawValue>,
+}
+
+// a payload or payloadAttributes with everything but the timestamp skipped
+#[derive(Debug, Deserialize)]
+pub struct TimestampOnly {
+    pub timestamp: Option<String>,
+}
+
+// a JSON-RPC error object, with the codes the engine API spec defines
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl JsonRpcError {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const SERVER_ERROR: i64 = -32000;
+    pub const LIMIT_EXCEEDED: i64 = -32005;
+    pub const UNKNOWN_PAYLOAD: i64 = -38001;
+    pub const INVALID_FORKCHOICE_STATE: i64 = -38002;
+    pub const INVALID_PAYLOAD_ATTRIBUTES: i64 = -38003;
+    pub const TOO_LARGE_REQUEST: i64 = -38004;
+    pub const UNSUPPORTED_FORK: i64 = -38005;
+
+    #[inline(always)]
+    pub fn new(code: i64, message: &str) -> Self {
+        JsonRpcError {
+            code,
+            message: message.to_owned(),
+            data: None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn with_data(mut self, data: &str) -> Self {
+        self.data = Some(serde_json::Value::String(data.to_owned()));
+        self
+    }
+
+    #[inline(always)]
+    pub fn parse_error() -> Self {
+        JsonRpcError::new(Self::PARSE_ERROR, "Parse error")
+    }
+
+    #[inline(always)]
+    pub fn invalid_request() -> Self {
+        JsonRpcError::new(Self::INVALID_REQUEST, "Invalid Request")
+    }
+
+    #[inline(always)]
+    pub fn method_not_found() -> Self {
+        JsonRpcError::new(Self::METHOD_NOT_FOUND, "Method not found")
+    }
+
+    #[inline(always)]
+    pub fn invalid_params(data: &str) -> Self {
+        JsonRpcError::new(Self::INVALID_PARAMS, "Invalid params").with_data(data)
+    }
+
+    #[inline(always)]
+    pub fn server_error(message: &str) -> Self {
+        JsonRpcError::new(Self::SERVER_ERROR, message)
+    }
+
+    // any error as the one a CL would get for it, ours keep their code and the rest are server errors
+    #[inline(always)]
+    pub fn from_error(e: &(dyn Error + 'static)) -> Self {
+        e.downcast_ref::<JsonRpcError>().cloned().unwrap_or_else(|| JsonRpcError::server_error(&e.to_string()))
+    }
+
+    #[inline(always)]
+    pub fn upstream_timeout(timeout: Duration) -> Self {
+        JsonRpcError::new(Self::SERVER_ERROR, &format!("The EL didn't answer within {}ms", timeout.as_millis()))
+    }
+
+    #[inline(always)]
+    pub fn el_unavailable() -> Self {
+        JsonRpcError::new(Self::SERVER_ERROR, "The EL is unavailable, try again later")
+    }
+
+    #[inline(always)]
+    pub fn limit_exceeded() -> Self {
+        JsonRpcError::new(Self::LIMIT_EXCEEDED, "Limit exceeded")
+    }
+
+    #[inline(always)]
+    pub fn unknown_payload() -> Self {
+        JsonRpcError::new(Self::UNKNOWN_PAYLOAD, "Unknown payload")
+    }
+
+    #[inline(always)]
+    pub fn invalid_forkchoice_state() -> Self {
+        JsonRpcError::new(Self::INVALID_FORKCHOICE_STATE, "Invalid forkchoice state")
+    }
+
+    #[inline(always)]
+    pub fn invalid_payload_attributes(data: &str) -> Self {
+        JsonRpcError::new(Self::INVALID_PAYLOAD_ATTRIBUTES, "Invalid payload attributes").with_data(data)
+    }
+
+    #[inline(always)]
+    pub fn too_large_request() -> Self {
+        JsonRpcError::new(Self::TOO_LARGE_REQUEST, "Too large request")
+    }
+
+    #[inline(always)]
+    pub fn unsupported_fork() -> Self {
+        JsonRpcError::new(Self::UNSUPPORTED_FORK, "Unsupported fork")
+    }
+
+    #[inline(always)]
+    pub fn to_response(&self, id: &serde_json::Value) -> String {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": self,
+        })
+        .to_string()
+    }
+}
+
+impl fmt::Display for JsonRpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl Error for JsonRpcError {}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct WithdrawalV1 {
-    pub index: String,
-    pub validatorIndex: String,
-    pub address: String,
-    pub amount: String,
+    pub index: U64,
+    pub validatorIndex: U64,
+    pub address: Address,
+    pub amount: U64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ForkchoiceStateV1 {
-    pub headBlockHash: String,
-    pub safeBlockHash: String,
-    pub finalizedBlockHash: String,
+    pub headBlockHash: H256,
+    pub safeBlockHash: H256,
+    pub finalizedBlockHash: H256,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PayloadAttributesV2 {
-    pub timestamp: String,
-    pub prevRandao: String,
-    pub suggestedFeeRecipient: String,
+    pub timestamp: U64,
+    pub prevRandao: H256,
+    pub suggestedFeeRecipient: Address,
     pub withdrawals: Option<Vec<WithdrawalV1>>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PayloadAttributesV3 {
+    pub timestamp: U64,
+    pub prevRandao: H256,
+    pub suggestedFeeRecipient: Address,
+    pub withdrawals: Vec<WithdrawalV1>,
+    pub parentBeaconBlockRoot: H256,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionPayloadV1 {
+    pub parentHash: H256,
+    pub feeRecipient: Address,
+    pub stateRoot: H256,
+    pub receiptsRoot: H256,
+    pub logsBloom: Bloom,
+    pub prevRandao: H256,
+    pub blockNumber: U64,
+    pub gasLimit: U64,
+    pub gasUsed: U64,
+    pub timestamp: U64,
+    pub extraData: Bytes,
+    pub baseFeePerGas: U256,
+    pub blockHash: H256,
+    pub transactions: Vec<Bytes>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionPayloadV2 {
-    pub parentHash: String,
-    pub feeRecipient: String,
-    pub stateRoot: String,
-    pub receiptsRoot: String,
-    pub logsBloom: String,
-    pub prevRandao: String,
-    pub blockNumber: String,
-    pub gasLimit: String,
-    pub gasUsed: String,
-    pub timestamp: String,
-    pub extraData: String,
-    pub baseFeePerGas: String,
-    pub blockHash: String,
-    pub transactions: Vec<String>,
+    pub parentHash: H256,
+    pub feeRecipient: Address,
+    pub stateRoot: H256,
+    pub receiptsRoot: H256,
+    pub logsBloom: Bloom,
+    pub prevRandao: H256,
+    pub blockNumber: U64,
+    pub gasLimit: U64,
+    pub gasUsed: U64,
+    pub timestamp: U64,
+    pub extraData: Bytes,
+    pub baseFeePerGas: U256,
+    pub blockHash: H256,
+    pub transactions: Vec<Bytes>,
+    pub withdrawals: Vec<WithdrawalV1>,
+}
+
+// newPayloadV2 takes a V1 payload before Shanghai and a V2 one after it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ExecutionPayloadV1OrV2 {
+    V2(ExecutionPayloadV2),
+    V1(ExecutionPayloadV1),
+}
+
+impl ExecutionPayloadV1OrV2 {
+    #[inline(always)]
+    pub fn block_hash(&self) -> H256 {
+        match self {
+            ExecutionPayloadV1OrV2::V2(p) => p.blockHash,
+            ExecutionPayloadV1OrV2::V1(p) => p.blockHash,
+        }
+    }
+
+    #[inline(always)]
+    pub fn parent_hash(&self) -> H256 {
+        match self {
+            ExecutionPayloadV1OrV2::V2(p) => p.parentHash,
+            ExecutionPayloadV1OrV2::V1(p) => p.parentHash,
+        }
+    }
+
+    #[inline(always)]
+    pub fn block_number(&self) -> u64 {
+        match self {
+            ExecutionPayloadV1OrV2::V2(p) => p.blockNumber.as_u64(),
+            ExecutionPayloadV1OrV2::V1(p) => p.blockNumber.as_u64(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn payload_body(&self) -> ExecutionPayloadBodyV1 {
+        match self {
+            ExecutionPayloadV1OrV2::V2(p) => ExecutionPayloadBodyV1 {
+                transactions: p.transactions.clone(),
+                withdrawals: Some(p.withdrawals.clone()),
+            },
+            ExecutionPayloadV1OrV2::V1(p) => ExecutionPayloadBodyV1 {
+                transactions: p.transactions.clone(),
+                withdrawals: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionPayloadV3 {
+    pub parentHash: H256,
+    pub feeRecipient: Address,
+    pub stateRoot: H256,
+    pub receiptsRoot: H256,
+    pub logsBloom: Bloom,
+    pub prevRandao: H256,
+    pub blockNumber: U64,
+    pub gasLimit: U64,
+    pub gasUsed: U64,
+    pub timestamp: U64,
+    pub extraData: Bytes,
+    pub baseFeePerGas: U256,
+    pub blockHash: H256,
+    pub transactions: Vec<Bytes>,
+    pub withdrawals: Vec<WithdrawalV1>,
+    pub blobGasUsed: U64,
+    pub excessBlobGas: U64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionPayloadBodyV1 {
+    pub transactions: Vec<Bytes>,
     pub withdrawals: Option<Vec<WithdrawalV1>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum ExecutionStatus {
     VALID,
     INVALID,
@@ -72,8 +351,48 @@ pub struct TransitionConfigurationV1 {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct payloadStatusV1 {
     pub status: ExecutionStatus,
-    pub latestValidHash: Option<String>,
-    pub ValidationError: Option<String>,
+    pub latestValidHash: Option<H256>,
+    pub validationError: Option<String>,
+}
+
+impl payloadStatusV1 {
+    #[inline(always)]
+    pub fn syncing() -> Self {
+        payloadStatusV1 {
+            status: ExecutionStatus::SYNCING,
+            latestValidHash: None,
+            validationError: None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn invalid(latest_valid_hash: Option<H256>, validation_error: &str) -> Self {
+        payloadStatusV1 {
+            status: ExecutionStatus::INVALID,
+            latestValidHash: latest_valid_hash,
+            validationError: Some(validation_error.to_owned()),
+        }
+    }
+
+    #[inline(always)]
+    pub fn invalid_block_hash(validation_error: &str) -> Self {
+        payloadStatusV1 {
+            status: ExecutionStatus::INVALID_BLOCK_HASH,
+            latestValidHash: None,
+            validationError: Some(validation_error.to_owned()),
+        }
+    }
+}
+
+// a response serialized with another id than its own, borrowing the rest of it rather than cloning it, so it's
+// the same json as the response with that id
+#[derive(Serialize)]
+struct ResponseWithId<'a, T> {
+    jsonrpc: &'a str,
+    id: &'a RpcId,
+    result: &'a T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a JsonRpcError>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,27 +404,51 @@ pub struct forkchoiceUpdatedV1ResponseResult {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct forkchoiceUpdatedV1Response {
     pub jsonrpc: String,
-    pub id: u64,
+    pub id: RpcId,
     pub result: forkchoiceUpdatedV1ResponseResult,
-    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
 }
 
 impl forkchoiceUpdatedV1Response {
+    // a response we make ourselves, there's never a payloadId since we don't build blocks
+    #[inline(always)]
+    pub fn from_status(id: &RpcId, status: payloadStatusV1) -> Self {
+        forkchoiceUpdatedV1Response {
+            jsonrpc: "2.0".to_owned(),
+            id: id.clone(),
+            result: forkchoiceUpdatedV1ResponseResult {
+                payloadStatus: status,
+                payloadId: None,
+            },
+            error: None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn syncing(id: &RpcId) -> Self {
+        forkchoiceUpdatedV1Response::from_status(id, payloadStatusV1::syncing())
+    }
+
+    #[inline(always)]
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string(self)?)
+    }
+
     #[inline(always)]
     pub fn to_db(&self) -> Result<String, Box<dyn Error>> {
         // we have to remove the id field
-        let mut fcu = self.clone();
-        fcu.id = 0;
-        let json = serde_json::to_string(&fcu)?;
-        Ok(json)
+        self.set_id(&RpcId::default())
     }
 
     #[inline(always)]
-    pub fn set_id(&self, id: u64) -> Result<String, Box<dyn Error>> {
-        // we have to set the id field
-        let mut fcu = self.clone();
-        fcu.id = id;
-        let json = serde_json::to_string(&fcu)?;
+    pub fn set_id(&self, id: &RpcId) -> Result<String, Box<dyn Error>> {
+        let json = serde_json::to_string(&ResponseWithId {
+            jsonrpc: &self.jsonrpc,
+            id,
+            result: &self.result,
+            error: self.error.as_ref(),
+        })?;
         Ok(json)
     }
 }
@@ -113,27 +456,57 @@ impl forkchoiceUpdatedV1Response {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct newPayloadV1Response {
     pub jsonrpc: String,
-    pub id: u64,
+    pub id: RpcId,
     pub result: payloadStatusV1,
-    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
 }
 
 impl newPayloadV1Response {
+    #[inline(always)]
+    pub fn from_status(id: &RpcId, status: payloadStatusV1) -> Self {
+        newPayloadV1Response {
+            jsonrpc: "2.0".to_owned(),
+            id: id.clone(),
+            result: status,
+            error: None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn syncing(id: &RpcId) -> Self {
+        newPayloadV1Response::from_status(id, payloadStatusV1::syncing())
+    }
+
+    #[inline(always)]
+    pub fn invalid(id: &RpcId, latest_valid_hash: Option<H256>, validation_error: &str) -> Self {
+        newPayloadV1Response::from_status(id, payloadStatusV1::invalid(latest_valid_hash, validation_error))
+    }
+
+    #[inline(always)]
+    pub fn invalid_block_hash(id: &RpcId, validation_error: &str) -> Self {
+        newPayloadV1Response::from_status(id, payloadStatusV1::invalid_block_hash(validation_error))
+    }
+
+    #[inline(always)]
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string(self)?)
+    }
+
     #[inline(always)]
     pub fn to_db(&self) -> Result<String, Box<dyn Error>> {
         // we have to remove the id field
-        let mut fcu = self.clone();
-        fcu.id = 0;
-        let json = serde_json::to_string(&fcu)?;
-        Ok(json)
+        self.set_id(&RpcId::default())
     }
 
     #[inline(always)]
-    pub fn set_id(&self, id: u64) -> Result<String, Box<dyn Error>> {
-        // we have to set the id field
-        let mut fcu = self.clone();
-        fcu.id = id;
-        let json = serde_json::to_string(&fcu)?;
+    pub fn set_id(&self, id: &RpcId) -> Result<String, Box<dyn Error>> {
+        let json = serde_json::to_string(&ResponseWithId {
+            jsonrpc: &self.jsonrpc,
+            id,
+            result: &self.result,
+            error: self.error.as_ref(),
+        })?;
         Ok(json)
     }
 }
@@ -141,7 +514,7 @@ impl newPayloadV1Response {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct forkchoiceUpdatedV2 {
     pub jsonrpc: String,
-    pub id: u64,
+    pub id: RpcId,
     pub method: String,
     pub params: (ForkchoiceStateV1, Option<PayloadAttributesV2>),
 }
@@ -150,34 +523,334 @@ impl forkchoiceUpdatedV2 {
     #[inline(always)]
     pub fn to_db(&self) -> Result<String, Box<dyn Error>> {
         // we have to remove the id field and if present remove the payloadAttributes
-        let mut fcu = self.clone();
-        fcu.id = 0;
+        let json = serde_json::to_string(&forkchoiceUpdatedForDb {
+            jsonrpc: &self.jsonrpc,
+            id: RpcId::default(),
+            method: &self.method,
+            params: (&self.params.0, None),
+        })?;
+        Ok(json)
+    }
+}
 
-        if fcu.params.1.is_some() {
-            fcu.params.1 = None;
-        }
+// a fcU of any version as to_db has it, borrowed from the request. the payloadAttributes are always null
+#[derive(Serialize)]
+struct forkchoiceUpdatedForDb<'a> {
+    jsonrpc: &'a str,
+    id: RpcId,
+    method: &'a str,
+    params: (&'a ForkchoiceStateV1, Option<()>),
+}
+
+// respose for forkchoiceUpdatedV2 is the same as forkchoiceUpdatedV1
 
-        let json = serde_json::to_string(&fcu)?;
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct forkchoiceUpdatedV3 {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub method: String,
+    pub params: (ForkchoiceStateV1, Option<PayloadAttributesV3>),
+}
+
+impl forkchoiceUpdatedV3 {
+    #[inline(always)]
+    pub fn to_db(&self) -> Result<String, Box<dyn Error>> {
+        // same as fcUV2, without the id and the payloadAttributes
+        let json = serde_json::to_string(&forkchoiceUpdatedForDb {
+            jsonrpc: &self.jsonrpc,
+            id: RpcId::default(),
+            method: &self.method,
+            params: (&self.params.0, None),
+        })?;
         Ok(json)
     }
 }
 
-// respose for forkchoiceUpdatedV2 is the same as forkchoiceUpdatedV1
+// respose for forkchoiceUpdatedV3 is the same as forkchoiceUpdatedV1
+// Prague keeps using fcUV3 with the same payloadAttributes, only newPayload and getPayload got a V4
+
+// lets the fcU handlers work on every version of the request
+pub trait ForkchoiceUpdatedRequest {
+    fn id(&self) -> &RpcId;
+    fn has_payload_attributes(&self) -> bool;
+    // the request with the id zeroed and the payloadAttributes removed, this is what last_legitimate_fcu holds.
+    // without payloadAttributes every version fits in a fcUV2, the method still tells them apart
+    fn normalized(&self) -> forkchoiceUpdatedV2;
+    // what the response is stored under in the db
+    fn cache_key(&self) -> String {
+        let fcu = self.normalized();
+        let state = &fcu.params.0;
+        cache_hash(&[
+            fcu.method.as_bytes(),
+            state.headBlockHash.0.as_bytes(),
+            state.safeBlockHash.0.as_bytes(),
+            state.finalizedBlockHash.0.as_bytes(),
+        ])
+    }
+}
+
+impl ForkchoiceUpdatedRequest for forkchoiceUpdatedV2 {
+    #[inline(always)]
+    fn id(&self) -> &RpcId {
+        &self.id
+    }
+
+    #[inline(always)]
+    fn has_payload_attributes(&self) -> bool {
+        self.params.1.is_some()
+    }
+
+    #[inline(always)]
+    fn normalized(&self) -> forkchoiceUpdatedV2 {
+        forkchoiceUpdatedV2 {
+            jsonrpc: self.jsonrpc.clone(),
+            id: RpcId::default(),
+            method: self.method.clone(),
+            params: (self.params.0.clone(), None),
+        }
+    }
+}
+
+impl ForkchoiceUpdatedRequest for forkchoiceUpdatedV3 {
+    #[inline(always)]
+    fn id(&self) -> &RpcId {
+        &self.id
+    }
+
+    #[inline(always)]
+    fn has_payload_attributes(&self) -> bool {
+        self.params.1.is_some()
+    }
+
+    #[inline(always)]
+    fn normalized(&self) -> forkchoiceUpdatedV2 {
+        forkchoiceUpdatedV2 {
+            jsonrpc: self.jsonrpc.clone(),
+            id: RpcId::default(),
+            method: self.method.clone(),
+            params: (self.params.0.clone(), None),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct newPayloadV2 {
     pub jsonrpc: String,
-    pub id: u64,
+    pub id: RpcId,
     pub method: String,
-    pub params: Vec<ExecutionPayloadV2>,
+    pub params: (ExecutionPayloadV1OrV2,),
 }
 
 // response for newPayloadV2 is the same as newPayloadV1
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct newPayloadV3 {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub method: String,
+    pub params: (ExecutionPayloadV3, Vec<H256>, H256), // payload, expectedBlobVersionedHashes, parentBeaconBlockRoot
+}
+
+// response for newPayloadV3 is the same as newPayloadV1
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct newPayloadV4 {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub method: String,
+    pub params: (ExecutionPayloadV3, Vec<H256>, H256, Vec<Bytes>), // payload, expectedBlobVersionedHashes, parentBeaconBlockRoot, executionRequests
+}
+
+// response for newPayloadV4 is the same as newPayloadV1
+
+// EIP-7685 requests hash: sha256 over the sha256 of every non-empty request
+#[inline(always)]
+pub fn requests_hash(execution_requests: &[Bytes]) -> String {
+    let mut outer = Sha256::new();
+    for request in execution_requests {
+        let request = request.to_vec().unwrap_or_else(|_| request.as_str().as_bytes().to_vec());
+        // the first byte is the request type, anything past that is the request data
+        if request.len() > 1 {
+            outer.update(Sha256::digest(&request));
+        }
+    }
+    format!("0x{}", hex::encode(outer.finalize()))
+}
+
+// the db key for a request: the keccak of the fields that identify it, each prefixed with its length.
+// clients serializing the same request differently (whitespace, field order, hex case) still end up on the same key
+pub fn cache_hash(fields: &[&[u8]]) -> String {
+    let mut hasher = Keccak256::new();
+    for field in fields {
+        hasher.update((field.len() as u64).to_be_bytes());
+        hasher.update(field);
+    }
+    format!("0x{}", hex::encode(hasher.finalize()))
+}
+
+// lets the newPayload handlers work on every version of the request
+pub trait NewPayloadRequest {
+    fn id(&self) -> &RpcId;
+    fn block_hash(&self) -> Option<H256>;
+    fn block_number(&self) -> Option<u64>;
+    fn parent_hash(&self) -> Option<H256>;
+    fn payload_body(&self) -> Option<ExecutionPayloadBodyV1>;
+    // the block hash recomputed from the payload fields, to check against block_hash
+    fn computed_block_hash(&self) -> Result<H256, String>;
+    // the block as eth_getBlockByHash answers with it, the transactions by their hashes
+    fn block(&self) -> Result<serde_json::Value, String>;
+    // what the response is stored under in the db
+    fn cache_key(&self) -> Option<String> {
+        self.block_hash().map(|v| cache_hash(&[v.0.as_bytes()]))
+    }
+}
+
+impl NewPayloadRequest for newPayloadV2 {
+    #[inline(always)]
+    fn id(&self) -> &RpcId {
+        &self.id
+    }
+
+    #[inline(always)]
+    fn block_hash(&self) -> Option<H256> {
+        Some(self.params.0.block_hash())
+    }
+
+    #[inline(always)]
+    fn block_number(&self) -> Option<u64> {
+        Some(self.params.0.block_number())
+    }
+
+    #[inline(always)]
+    fn parent_hash(&self) -> Option<H256> {
+        Some(self.params.0.parent_hash())
+    }
+
+    #[inline(always)]
+    fn payload_body(&self) -> Option<ExecutionPayloadBodyV1> {
+        Some(self.params.0.payload_body())
+    }
+
+    #[inline(always)]
+    fn computed_block_hash(&self) -> Result<H256, String> {
+        match &self.params.0 {
+            ExecutionPayloadV1OrV2::V2(p) => blockhash::payload_v2_block_hash(p),
+            ExecutionPayloadV1OrV2::V1(p) => blockhash::payload_v1_block_hash(p),
+        }
+    }
+
+    #[inline(always)]
+    fn block(&self) -> Result<serde_json::Value, String> {
+        match &self.params.0 {
+            ExecutionPayloadV1OrV2::V2(p) => blockhash::payload_v2_block(p),
+            ExecutionPayloadV1OrV2::V1(p) => blockhash::payload_v1_block(p),
+        }
+    }
+}
+
+impl NewPayloadRequest for newPayloadV3 {
+    #[inline(always)]
+    fn id(&self) -> &RpcId {
+        &self.id
+    }
+
+    #[inline(always)]
+    fn block_hash(&self) -> Option<H256> {
+        Some(self.params.0.blockHash)
+    }
+
+    #[inline(always)]
+    fn block_number(&self) -> Option<u64> {
+        Some(self.params.0.blockNumber.as_u64())
+    }
+
+    #[inline(always)]
+    fn parent_hash(&self) -> Option<H256> {
+        Some(self.params.0.parentHash)
+    }
+
+    #[inline(always)]
+    fn payload_body(&self) -> Option<ExecutionPayloadBodyV1> {
+        Some(ExecutionPayloadBodyV1 {
+            transactions: self.params.0.transactions.clone(),
+            withdrawals: Some(self.params.0.withdrawals.clone()),
+        })
+    }
+
+    #[inline(always)]
+    fn computed_block_hash(&self) -> Result<H256, String> {
+        blockhash::payload_v3_block_hash(&self.params.0, &self.params.2, None)
+    }
+
+    #[inline(always)]
+    fn block(&self) -> Result<serde_json::Value, String> {
+        blockhash::payload_v3_block(&self.params.0, &self.params.2, None)
+    }
+
+    #[inline(always)]
+    fn cache_key(&self) -> Option<String> {
+        // the expectedBlobVersionedHashes aren't in the block hash either
+        let mut fields = vec![self.params.0.blockHash.0.as_bytes()];
+        fields.extend(self.params.1.iter().map(|v| v.0.as_bytes()));
+        Some(cache_hash(&fields))
+    }
+}
+
+impl NewPayloadRequest for newPayloadV4 {
+    #[inline(always)]
+    fn id(&self) -> &RpcId {
+        &self.id
+    }
+
+    #[inline(always)]
+    fn block_hash(&self) -> Option<H256> {
+        Some(self.params.0.blockHash)
+    }
+
+    #[inline(always)]
+    fn block_number(&self) -> Option<u64> {
+        Some(self.params.0.blockNumber.as_u64())
+    }
+
+    #[inline(always)]
+    fn parent_hash(&self) -> Option<H256> {
+        Some(self.params.0.parentHash)
+    }
+
+    #[inline(always)]
+    fn payload_body(&self) -> Option<ExecutionPayloadBodyV1> {
+        Some(ExecutionPayloadBodyV1 {
+            transactions: self.params.0.transactions.clone(),
+            withdrawals: Some(self.params.0.withdrawals.clone()),
+        })
+    }
+
+    #[inline(always)]
+    fn computed_block_hash(&self) -> Result<H256, String> {
+        let requests_hash: H256 = requests_hash(&self.params.3).parse()?;
+        blockhash::payload_v3_block_hash(&self.params.0, &self.params.2, Some(&requests_hash))
+    }
+
+    #[inline(always)]
+    fn block(&self) -> Result<serde_json::Value, String> {
+        let requests_hash: H256 = requests_hash(&self.params.3).parse()?;
+        blockhash::payload_v3_block(&self.params.0, &self.params.2, Some(&requests_hash))
+    }
+
+    #[inline(always)]
+    fn cache_key(&self) -> Option<String> {
+        // the executionRequests are not part of the payload, so the block hash alone doesn't identify the request
+        let requests_hash = requests_hash(&self.params.3);
+        let mut fields = vec![self.params.0.blockHash.0.as_bytes(), requests_hash.as_bytes()];
+        fields.extend(self.params.1.iter().map(|v| v.0.as_bytes()));
+        Some(cache_hash(&fields))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct exchangeTransitionConfigurationV1 {
     pub jsonrpc: String,
-    pub id: u64,
+    pub id: RpcId,
     pub method: Option<String>,
     pub params: Option<Vec<TransitionConfigurationV1>>,
     pub result: Option<TransitionConfigurationV1>,
@@ -187,100 +860,493 @@ impl exchangeTransitionConfigurationV1 {
     #[inline(always)]
     pub fn to_db(&self) -> Result<String, Box<dyn Error>> {
         // we have to remove the id field
-        let mut fcu = self.clone();
-        fcu.id = 0;
-        let json = serde_json::to_string(&fcu)?;
-        Ok(json)
+        self.set_id(&RpcId::default())
     }
 
     #[inline(always)]
-    pub fn set_id(&self, id: u64) -> Result<String, Box<dyn Error>> {
-        // we have to set the id field
-        let mut fcu = self.clone();
-        fcu.id = id;
-        let json = serde_json::to_string(&fcu)?;
+    pub fn set_id(&self, id: &RpcId) -> Result<String, Box<dyn Error>> {
+        let json = serde_json::to_string(&exchangeTransitionConfigurationWithId {
+            jsonrpc: &self.jsonrpc,
+            id,
+            method: &self.method,
+            params: &self.params,
+            result: &self.result,
+        })?;
         Ok(json)
     }
 }
 
+// exchangeTransitionConfigurationV1 with another id, like ResponseWithId
+#[derive(Serialize)]
+struct exchangeTransitionConfigurationWithId<'a> {
+    jsonrpc: &'a str,
+    id: &'a RpcId,
+    method: &'a Option<String>,
+    params: &'a Option<Vec<TransitionConfigurationV1>>,
+    result: &'a Option<TransitionConfigurationV1>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobsBundleV1 {
+    pub commitments: Vec<String>,
+    pub proofs: Vec<String>,
+    pub blobs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getPayloadV3ResponseResult {
+    pub executionPayload: ExecutionPayloadV3,
+    pub blockValue: String,
+    pub blobsBundle: BlobsBundleV1,
+    pub shouldOverrideBuilder: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getPayloadV3Response {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub result: getPayloadV3ResponseResult,
+    pub error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getPayloadV4ResponseResult {
+    pub executionPayload: ExecutionPayloadV3,
+    pub blockValue: String,
+    pub blobsBundle: BlobsBundleV1,
+    pub shouldOverrideBuilder: bool,
+    pub executionRequests: Vec<Bytes>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getPayloadV4Response {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub result: getPayloadV4ResponseResult,
+    pub error: Option<JsonRpcError>,
+}
+
+// lets the getPayload handler log every version of the response
+pub trait GetPayloadResponse {
+    fn execution_payload(&self) -> &ExecutionPayloadV3;
+    fn block_value(&self) -> &str;
+    fn blobs_bundle(&self) -> &BlobsBundleV1;
+    fn should_override_builder(&self) -> bool;
+    fn execution_requests(&self) -> Option<&[Bytes]> {
+        None
+    }
+}
+
+impl GetPayloadResponse for getPayloadV3Response {
+    #[inline(always)]
+    fn execution_payload(&self) -> &ExecutionPayloadV3 {
+        &self.result.executionPayload
+    }
+
+    #[inline(always)]
+    fn block_value(&self) -> &str {
+        &self.result.blockValue
+    }
+
+    #[inline(always)]
+    fn blobs_bundle(&self) -> &BlobsBundleV1 {
+        &self.result.blobsBundle
+    }
+
+    #[inline(always)]
+    fn should_override_builder(&self) -> bool {
+        self.result.shouldOverrideBuilder
+    }
+}
+
+impl GetPayloadResponse for getPayloadV4Response {
+    #[inline(always)]
+    fn execution_payload(&self) -> &ExecutionPayloadV3 {
+        &self.result.executionPayload
+    }
+
+    #[inline(always)]
+    fn block_value(&self) -> &str {
+        &self.result.blockValue
+    }
+
+    #[inline(always)]
+    fn blobs_bundle(&self) -> &BlobsBundleV1 {
+        &self.result.blobsBundle
+    }
+
+    #[inline(always)]
+    fn should_override_builder(&self) -> bool {
+        self.result.shouldOverrideBuilder
+    }
+
+    #[inline(always)]
+    fn execution_requests(&self) -> Option<&[Bytes]> {
+        Some(&self.result.executionRequests)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getPayloadBodiesByHashV1 {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub method: String,
+    pub params: (Vec<H256>,), // block hashes
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getPayloadBodiesByRangeV1 {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub method: String,
+    pub params: (U64, U64), // start, count
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getPayloadBodiesV1Response {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub result: Vec<Option<ExecutionPayloadBodyV1>>,
+    pub error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct exchangeCapabilities {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub method: String,
+    pub params: (Vec<String>,), // the methods the CL supports
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct exchangeCapabilitiesResponse {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub result: Vec<String>,
+    pub error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getBlobsV1 {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub method: String,
+    pub params: (Vec<H256>,), // versioned hashes
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobAndProofV1 {
+    pub blob: String,
+    pub proof: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getBlobsV1Response {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub result: Vec<Option<BlobAndProofV1>>,
+    pub error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientVersionV1 {
+    pub code: String,
+    pub name: String,
+    pub version: String,
+    pub commit: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getClientVersionV1 {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub method: String,
+    pub params: (ClientVersionV1,), // the version of the CL asking
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct getClientVersionV1Response {
+    pub jsonrpc: String,
+    pub id: RpcId,
+    pub result: Vec<ClientVersionV1>,
+    pub error: Option<JsonRpcError>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RequestMethod {
     engine_ForkchoiceUpdatedV1,
     engine_ForkchoiceUpdatedV2,
+    engine_ForkchoiceUpdatedV3,
     engine_NewPayloadV1,
     engine_NewPayloadV2,
+    engine_NewPayloadV3,
+    engine_NewPayloadV4,
     engine_getPayloadV1,
     engine_getPayloadV2,
+    engine_getPayloadV3,
+    engine_getPayloadV4,
     engine_getPayloadBodiesByHashV1,
     engine_getPayloadBodiesByRangeV1,
     engine_exchangeCapabilities,
     engine_exchangeTransitionConfigurationV1,
+    engine_getBlobsV1,
+    engine_getClientVersionV1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Fork {
+    Paris,
+    Shanghai,
+    Cancun,
+    Prague,
+}
+
+// the most bytes a request can have before it's answered with -38004. a newPayload carries a whole block
+// so followers get more room for it than for everything else, the controlling CL has one limit for all
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimits {
+    pub client: usize,
+    pub client_payload: usize,
+    pub canonical: usize,
+}
+
+impl BodyLimits {
+    // a follower's body is read up to the larger of its limits, the method it's for isn't known before that
+    #[inline(always)]
+    pub fn client_body(&self) -> usize {
+        self.client.max(self.client_payload)
+    }
+
+    #[inline(always)]
+    pub fn client_method(&self, method: &str) -> usize {
+        match method.starts_with("engine_newPayload") {
+            true => self.client_payload,
+            false => self.client,
+        }
+    }
+}
+
+// how a request is sent to the EL
+#[derive(Debug, Clone, Copy)]
+pub struct UpstreamCall {
+    pub method: &'static str, // the method without its version, or "other", for the metrics
+    pub timeout: Duration,    // for every attempt together
+    pub retries: u32,         // after a connection error, 0 for a method that isn't safe to send twice
+}
+
+// how long the EL gets to answer a request, the engine API spec's timeout for its methods and `other` for the rest,
+// and which methods can be sent again when the connection to it fails
+#[derive(Debug, Clone, Copy)]
+pub struct UpstreamPolicy {
+    pub other: Duration,
+    pub retries: u32,
+}
+
+impl UpstreamPolicy {
+    // any version of a method, getPayloadBodies before getPayload since it starts the same.
+    // a repeated newPayload is fine, the EL answers the same block with the same status
+    #[inline(always)]
+    pub fn get(&self, method: &str) -> UpstreamCall {
+        const SPEC: [(&str, u64, bool); 8] = [
+            ("engine_newPayload", 8, true),
+            ("engine_forkchoiceUpdated", 8, false),
+            ("engine_getPayloadBodies", 10, true),
+            ("engine_getPayload", 1, false),
+            ("engine_getBlobs", 1, true),
+            ("engine_exchangeCapabilities", 1, true),
+            ("engine_exchangeTransitionConfiguration", 1, true),
+            ("engine_getClientVersion", 1, true),
+        ];
+        let (method, timeout, retry) = SPEC
+            .iter()
+            .find(|(prefix, _, _)| method.starts_with(prefix))
+            .map(|(prefix, secs, retry)| (*prefix, Duration::from_secs(*secs), *retry))
+            .unwrap_or(("other", self.other, false));
+        UpstreamCall {
+            method,
+            timeout,
+            retries: if retry { self.retries } else { 0 },
+        }
+    }
+}
+
+// activation timestamps, a fork we don't know the timestamp of is never considered active
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ForkSchedule {
+    pub shanghai: Option<u64>,
+    pub cancun: Option<u64>,
+    pub prague: Option<u64>,
+}
+
+impl ForkSchedule {
+    #[inline(always)]
+    pub fn fork_at(&self, timestamp: u64) -> Fork {
+        let active = |fork_time: Option<u64>| fork_time.is_some_and(|v| timestamp >= v);
+        if active(self.prague) {
+            Fork::Prague
+        } else if active(self.cancun) {
+            Fork::Cancun
+        } else if active(self.shanghai) {
+            Fork::Shanghai
+        } else {
+            Fork::Paris
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_configured(&self, fork: Fork) -> bool {
+        match fork {
+            Fork::Paris => true,
+            Fork::Shanghai => self.shanghai.is_some(),
+            Fork::Cancun => self.cancun.is_some(),
+            Fork::Prague => self.prague.is_some(),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct State {
-    pub db: Arc<tokio_postgres::Client>,
-    pub jwt_encoding_secret: Arc<jsonwebtoken::EncodingKey>,
-    pub jwt_decoding_secret: Arc<jsonwebtoken::DecodingKey>,
-    pub auth_node: Arc<Node>,
-    pub unauth_node: Arc<Node>,
+    pub db: Arc<dyn crate::storage::Storage>,
+    pub jwt_keys: Arc<RwLock<crate::auth::JwtKeys>>, // swapped out when the secret file changes
+    pub auth_nodes: Arc<crate::nodes::NodePool>, // --node and the --backup-nodes
+    pub verify_nodes: Vec<Arc<Node>>,            // ELs the controlling CL's blocks are checked against too
+    pub unauth_node: Option<Arc<Node>>, // None with --no-unauth, the auth node takes its requests
     pub last_legitimate_fcu: Arc<RwLock<Option<fcu_pair>>>, // first should be req second should be res
+    pub canonical_head: Arc<std::sync::RwLock<Option<(H256, bool)>>>, // the controlling CL's last fcU's head, and whether the EL was at it
+    pub blob_cache: Arc<RwLock<HashMap<H256, (Instant, BlobAndProofV1)>>>, // versioned hash -> (when we got it, blob)
+    pub el_client_version: Arc<RwLock<Option<Vec<ClientVersionV1>>>>,
+    pub el_web3_version: Arc<RwLock<Option<String>>>, // the EL's web3_clientVersion
+    pub el_chain_id: Arc<std::sync::RwLock<Option<u64>>>, // asked again every minute
+    pub capabilities: Arc<RwLock<Option<Vec<String>>>>, // what both the EL and openexecution support
+    pub fork_schedule: ForkSchedule,
+    pub verify_block_hash: bool, // recompute the blockHash of newPayloads before forwarding them
+    pub forward_unknown_engine: bool, // send the controlling CL's engine_ methods OE doesn't know to the EL
+    pub client_secrets: Option<Arc<RwLock<crate::auth::ClientSecrets>>>, // None lets any follower in
+    pub jwt_max_iat_skew: u64, // seconds
+    pub admin_auth: Option<Arc<RwLock<crate::auth::AdminAuth>>>, // None lets anyone who reaches the admin API in
+    pub client_ip_filter: Arc<std::sync::RwLock<crate::access::IpFilter>>, // checked before anything else on the follower routes, swapped on a config reload
+    pub client_rate_limiter: Arc<std::sync::RwLock<crate::ratelimit::RateLimiter>>, // per follower and method, swapped on a config reload
+    pub client_methods: Arc<std::sync::RwLock<crate::methods::MethodFilter>>, // the non-engine_ calls followers can make, swapped on a config reload
+    pub method_policy: Arc<std::sync::RwLock<crate::policy::MethodPolicy>>, // forward, cache or reject per method and client, swapped on a config reload
+    pub body_limits: BodyLimits,
+    pub shutdown: Arc<crate::shutdown::Shutdown>, // lets the websockets finish up when openexecution stops
+    pub metrics: Arc<crate::metrics::Metrics>,
+    pub recent_blocks: Arc<crate::heads::RecentBlocks>, // from the controlling CL's newPayloads, for the followers' head lag
+    pub recent_payloads: Arc<crate::blocks::RecentPayloads>, // the last valid payloads as blocks, for the followers' eth_getBlockBy*
+    pub new_heads: Arc<crate::subscriptions::NewHeads>, // the controlling CL's heads, for the followers' newHeads subscriptions
+    pub clients: Arc<crate::clients::ClientRegistry>, // every follower seen, kept in the db
+    pub bans: Arc<crate::bans::Bans>,                 // followers an admin turned away, kept in the db
+    pub maintenance: Arc<crate::maintenance::Maintenance>, // set by an admin while the EL is restarted or pruned
+    pub fcu_max_age: Option<Duration>, // past this the last fcU is too old to answer followers with, None never
+    pub fcu_stale: Arc<std::sync::atomic::AtomicBool>, // whether it's been warned the last fcU is too old
+    pub block_builders: Arc<std::sync::RwLock<std::collections::HashSet<String>>>, // followers whose payloadAttributes go to the EL
+    pub fee_recipients: Arc<crate::feerecipient::FeeRecipients>, // what those payloadAttributes' suggestedFeeRecipient becomes
+    pub payload_ids: Arc<crate::payloads::PayloadIds>,      // the ones the block builders were given
+    pub fcu_lookups: Arc<crate::inflight::InFlight<Result<Option<forkchoiceUpdatedV1Response>, JsonRpcError>>>, // followers' fcUs by forkchoiceState
+    pub fcu_builds: Arc<crate::inflight::InFlight<Result<String, JsonRpcError>>>, // block builders' fcUs sent to the EL
+    pub failover: Arc<crate::failover::Failover>,           // who controls the EL, the CL on /canonical or a standby
+    pub chain_check: Option<Arc<crate::chainid::ChainCheck>>, // None without a --chain-id or --network
 }
 
-#[derive(Debug, Clone)]
+impl State {
+    // the node engine_ calls go to, the first of auth_nodes that's up
+    #[inline(always)]
+    pub fn auth_node(&self) -> Arc<Node> {
+        self.auth_nodes.active()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct fcu_pair {
-    pub req: forkchoiceUpdatedV2,
+    pub req: forkchoiceUpdatedV2, // normalized, see ForkchoiceUpdatedRequest
     pub resp: forkchoiceUpdatedV1Response,
+    #[serde(default = "chrono::Utc::now")]
+    pub received: chrono::DateTime<chrono::Utc>, // saved with it, so its age carries across a restart
 }
 
-#[derive(Debug, Clone)]
 pub struct Node {
+    pub name: String, // what the metrics call it
     pub url: String,
     pub client: reqwest::Client,
+    pub upstream: UpstreamPolicy, // its own --node-timeout and --node-retries with --node-settings
+    pub jwt: Option<jsonwebtoken::EncodingKey>, // signs its requests instead of --el-jwt-secret when set
+    pub breaker: crate::breaker::CircuitBreaker, // whether followers get to wait on this node
+    pub metrics: Arc<crate::metrics::Metrics>,
+}
+
+// how the connections to the EL are kept, every fcU and newPayload waits on one of them
+#[derive(Debug, Clone)]
+pub struct NodeClientOptions {
+    pub pool_size: Option<usize>, // most idle connections kept to a node, unlimited if None
+    pub idle_timeout: Option<Duration>, // how long an idle connection is kept, forever if None
+    pub tcp_keepalive: Option<Duration>,
+    pub http2: bool, // talk http2 without negotiating it, the node has to support it
+    pub nodelay: bool,
+    pub compression: bool, // ask for gzip or deflate answers, worth it when the EL is across a network
+}
+
+impl NodeClientOptions {
+    pub fn builder(&self) -> reqwest::ClientBuilder {
+        let mut builder = reqwest::Client::builder()
+            .pool_idle_timeout(self.idle_timeout)
+            .tcp_keepalive(self.tcp_keepalive)
+            .tcp_nodelay(self.nodelay)
+            .gzip(self.compression)
+            .deflate(self.compression);
+        if let Some(pool_size) = self.pool_size {
+            builder = builder.pool_max_idle_per_host(pool_size);
+        }
+        if self.http2 {
+            builder = builder.http2_prior_knowledge();
+        }
+        builder
+    }
 }
 
 // custom writer that writes to multiple writers
-pub struct MultiWriter<W1> {
-    pub writer1: Arc<Mutex<W1>>,
+#[derive(Clone, Default)]
+pub struct MultiWriter {
+    writers: Vec<Arc<Mutex<dyn std::io::Write + Send>>>,
 }
 
-impl<W1> MultiWriter<W1>
-where
-    W1: std::io::Write,
-{
-    pub fn new(writer1: Arc<Mutex<W1>>) -> Self {
-        MultiWriter { writer1 }
+impl MultiWriter {
+    pub fn new() -> Self {
+        MultiWriter::default()
+    }
+
+    pub fn with(mut self, writer: impl std::io::Write + Send + 'static) -> Self {
+        self.writers.push(Arc::new(Mutex::new(writer)));
+        self
     }
 }
 
-impl<W1> std::io::Write for MultiWriter<W1>
-where
-    W1: std::io::Write,
-{
+impl std::io::Write for MultiWriter {
+    // every writer gets the whole line, one that fails doesn't keep it from the others
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let mut writer1 = self.writer1.lock().unwrap();
-        let len1 = writer1.write(buf)?;
-        Ok(len1)
+        let mut result = Ok(buf.len());
+        for writer in &self.writers {
+            if let Err(e) = writer.lock().unwrap().write_all(buf) {
+                result = Err(e);
+            }
+        }
+        result
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        let mut writer1 = self.writer1.lock().unwrap();
-        writer1.flush()?;
-        Ok(())
+        let mut result = Ok(());
+        for writer in &self.writers {
+            if let Err(e) = writer.lock().unwrap().flush() {
+                result = Err(e);
+            }
+        }
+        result
     }
 }
 
-impl<'a, W1> MakeWriter<'a> for MultiWriter<W1>
-where
-    W1: std::io::Write + Send + Sync + 'static,
-{
-    type Writer = MultiWriter<W1>;
+impl<'a> MakeWriter<'a> for MultiWriter {
+    type Writer = MultiWriter;
 
     fn make_writer(&'a self) -> Self::Writer {
-        MultiWriter::new(self.writer1.clone())
-    }
-
-    fn make_writer_for(&'a self, _: &tracing::Metadata<'_>) -> Self::Writer {
-        MultiWriter::new(self.writer1.clone())
+        self.clone()
     }
 }